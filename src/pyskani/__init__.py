@@ -1,5 +1,5 @@
 from . import _skani
-from ._skani import Sketch, Database, Hit
+from ._skani import Sketch, SketchParameters, Database, Hit, SkaniError, dist, merge
 
 __version__ = _skani.__version__
 __author__ = _skani.__author__
@@ -7,8 +7,12 @@ __doc__ = _skani.__doc__
 __build__ = _skani.__build__
 __all__ = [
     "Sketch",
+    "SketchParameters",
     "Database",
     "Hit",
+    "SkaniError",
+    "dist",
+    "merge",
     "SKANI_VERSION",
 ]
 
@@ -2,9 +2,11 @@ import os
 from array import array
 from pathlib import Path
 from types import TracebackType
-from typing import Dict, Union, Optional, Type, List, Literal
+from typing import Any, Callable, Dict, Union, Optional, Type, List, Literal, Iterable, Iterator, Tuple, TypeVar, overload
 
 _FORMAT = Literal["consolidated", "separated"]
+_DUPLICATES = Literal["error", "skip", "replace"]
+_T = TypeVar("_T")
 
 _Path = Union[str, bytes, os.PathLike[str]]
 _Sequence = Union[str, bytes, bytearray, memoryview, array]
@@ -21,8 +23,22 @@ class Hit:
         query_fraction: float,
         reference_name: str,
         reference_fraction: float,
+        query_contig_name: Optional[str] = None,
+        query_contig_count: int = 0,
+        query_contig_length_50: float = 0.0,
+        query_contig_length_90: float = 0.0,
+        reference_contig_name: Optional[str] = None,
+        reference_contig_count: int = 0,
+        reference_contig_length_50: float = 0.0,
+        reference_contig_length_90: float = 0.0,
+        standard_deviation: float = 0.0,
+        ci_lower: float = 0.0,
+        ci_upper: float = 0.0,
     ) -> None: ...
     def __repr__(self) -> str: ...
+    def __reduce__(self) -> Tuple[Callable[[bytes], Hit], Tuple[bytes]]: ...
+    @classmethod
+    def _from_state(cls, state: bytes) -> Hit: ...
     @property
     def identity(self) -> float: ...
     @property
@@ -33,25 +49,113 @@ class Hit:
     def reference_name(self) -> str: ...
     @property
     def reference_fraction(self) -> float: ...
+    @property
+    def query_contig_name(self) -> str: ...
+    @property
+    def query_contig_count(self) -> int: ...
+    @property
+    def query_contig_length_50(self) -> float: ...
+    @property
+    def query_contig_length_90(self) -> float: ...
+    @property
+    def reference_contig_name(self) -> str: ...
+    @property
+    def reference_contig_count(self) -> int: ...
+    @property
+    def reference_contig_length_50(self) -> float: ...
+    @property
+    def reference_contig_length_90(self) -> float: ...
+    @property
+    def standard_deviation(self) -> float: ...
+    @property
+    def ci_lower(self) -> float: ...
+    @property
+    def ci_upper(self) -> float: ...
+
+class SkaniError(RuntimeError): ...
+
+class SketchParameters:
+    def __init__(
+        self,
+        compression: Optional[int] = None,
+        marker_compression: Optional[int] = None,
+        k: Optional[int] = None,
+        amino_acid: bool = False,
+    ) -> None: ...
+    def __repr__(self) -> str: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def __reduce__(self) -> Tuple[Type[SketchParameters], Tuple[int, int, int, bool]]: ...
+    @property
+    def compression(self) -> int: ...
+    @property
+    def marker_compression(self) -> int: ...
+    @property
+    def k(self) -> int: ...
+    @property
+    def amino_acid(self) -> bool: ...
+    @property
+    def use_syncs(self) -> bool: ...
+    @property
+    def orf_size(self) -> int: ...
 
 class Sketch:
+    @classmethod
+    def from_contigs(
+        cls,
+        name: str,
+        *contigs: _Sequence,
+        parameters: Optional[SketchParameters] = None,
+        compression: Optional[int] = None,
+        marker_compression: Optional[int] = None,
+        k: Optional[int] = None,
+        seed: bool = True,
+    ) -> Sketch: ...
+    def __reduce__(self) -> Tuple[Callable[[bytes], Sketch], Tuple[bytes]]: ...
+    @classmethod
+    def _from_state(cls, state: bytes) -> Sketch: ...
     @property
     def name(self) -> str: ...
     @property
+    def c(self) -> int: ...
+    @property
     def amino_acid(self) -> bool: ...
+    @property
+    def parameters(self) -> SketchParameters: ...
 
 class Database:
     @classmethod
     def load(cls, path: _Path) -> Database: ...
     @classmethod
-    def open(cls, path: _Path) -> Database: ...
+    def open(
+        cls,
+        path: _Path,
+        mode: Literal["r", "a"] = "a",
+        cache_size: int = 0,
+    ) -> Database: ...
+    @classmethod
+    def from_files(
+        cls,
+        paths: Iterable[_Path],
+        path: Optional[_Path] = None,
+        *,
+        parameters: Optional[SketchParameters] = None,
+        compression: Optional[int] = None,
+        marker_compression: Optional[int] = None,
+        k: Optional[int] = None,
+        format: Optional[_FORMAT] = None,
+        duplicates: _DUPLICATES = "error",
+        seed: bool = True,
+        threads: int = 0,
+    ) -> Database: ...
     def __init__(
         self,
         path: Union[str, bytes, os.PathLike[str], None] = None,
         *,
-        compression: int = ...,
-        marker_compression: int = ...,
-        k: int = ...,
+        parameters: Optional[SketchParameters] = None,
+        compression: Optional[int] = None,
+        marker_compression: Optional[int] = None,
+        k: Optional[int] = None,
         format: Optional[_FORMAT] = None,
     ) -> None: ...
     def __enter__(self) -> Database: ...
@@ -61,13 +165,36 @@ class Database:
         exc: Optional[BaseException],
         traceback: Optional[TracebackType],
     ) -> Optional[bool]: ...
+    def __reduce__(self) -> Tuple[Any, ...]: ...
+    def __setstate__(self, state: bytes) -> None: ...
     @property
     def path(self) -> Optional[Path]: ...
     @property
     def compression(self) -> int: ...
     @property
     def marker_compression(self) -> int: ...
-    def sketch(self, name: str, *contigs: _Sequence, seed: bool = True) -> None: ...
+    @property
+    def parameters(self) -> SketchParameters: ...
+    def sketch(self, name: str, *contigs: _Sequence, seed: bool = True) -> Sketch: ...
+    def sketch_contigs(
+        self, name: str, *contigs: _Sequence, seed: bool = True
+    ) -> List[Sketch]: ...
+    def sketch_file(
+        self, path: _Path, name: Optional[str] = None, *, seed: bool = True
+    ) -> Sketch: ...
+    def merge(self, other: Database, *, duplicates: _DUPLICATES = "error") -> int: ...
+    def add(self, sketch: Sketch) -> None: ...
+    def remove(self, name: str) -> None: ...
+    def __delitem__(self, name: str) -> None: ...
+    def __len__(self) -> int: ...
+    def __contains__(self, name: object) -> bool: ...
+    def __iter__(self) -> Iterator[str]: ...
+    def __getitem__(self, name: str) -> Sketch: ...
+    def names(self) -> List[str]: ...
+    @overload
+    def get(self, name: str) -> Optional[Sketch]: ...
+    @overload
+    def get(self, name: str, default: _T) -> Union[Sketch, _T]: ...
     def query(
         self,
         name: str,
@@ -78,6 +205,111 @@ class Database:
         robust: bool = False,
         cutoff: Optional[float] = None,
         faster_small: bool = False,
+        est_ci: bool = False,
+        individual_contigs: bool = False,
+        threads: int = 0,
     ) -> List[Hit]: ...
-    def save(self, path: _Path, format: Optional[_FORMAT] = None) -> None: ...
+    def query_file(
+        self,
+        path: _Path,
+        name: Optional[str] = None,
+        *,
+        seed: bool = True,
+        learned_ani: Optional[bool] = None,
+        median: bool = False,
+        robust: bool = False,
+        cutoff: Optional[float] = None,
+        faster_small: bool = False,
+        est_ci: bool = False,
+        individual_contigs: bool = False,
+        threads: int = 0,
+    ) -> List[Hit]: ...
+    def query_sketch(
+        self,
+        sketch: Sketch,
+        *,
+        learned_ani: Optional[bool] = None,
+        median: bool = False,
+        robust: bool = False,
+        cutoff: Optional[float] = None,
+        faster_small: bool = False,
+        est_ci: bool = False,
+        threads: int = 0,
+    ) -> List[Hit]: ...
+    def query_many(
+        self,
+        genomes: Iterable[Tuple[str, Iterable[_Sequence]]],
+        *,
+        seed: bool = True,
+        learned_ani: Optional[bool] = None,
+        median: bool = False,
+        robust: bool = False,
+        cutoff: Optional[float] = None,
+        faster_small: bool = False,
+        est_ci: bool = False,
+        threads: int = 0,
+    ) -> List[List[Hit]]: ...
+    @overload
+    def triangle(
+        self,
+        *,
+        sparse: Literal[False] = False,
+        diagonal: bool = False,
+        distance: bool = False,
+        learned_ani: Optional[bool] = None,
+        median: bool = False,
+        robust: bool = False,
+        cutoff: Optional[float] = None,
+        faster_small: bool = False,
+        est_ci: bool = False,
+        threads: int = 0,
+    ) -> Tuple[List[str], List[List[float]], List[List[float]], List[List[float]]]: ...
+    @overload
+    def triangle(
+        self,
+        *,
+        sparse: Literal[True],
+        diagonal: bool = False,
+        distance: bool = False,
+        learned_ani: Optional[bool] = None,
+        median: bool = False,
+        robust: bool = False,
+        cutoff: Optional[float] = None,
+        faster_small: bool = False,
+        est_ci: bool = False,
+        threads: int = 0,
+    ) -> List[Hit]: ...
+    def save(
+        self,
+        path: _Path,
+        overwrite: bool = False,
+        format: Optional[_FORMAT] = None,
+    ) -> None: ...
+    def compact(self) -> int: ...
+    def cache_info(self) -> Dict[str, int]: ...
     def flush(self) -> None: ...
+    def close(self) -> None: ...
+
+def dist(
+    reference: Union[_Sequence, Iterable[_Sequence]],
+    query: Union[_Sequence, Iterable[_Sequence]],
+    *,
+    reference_name: str = "reference",
+    query_name: str = "query",
+    parameters: Optional[SketchParameters] = None,
+    compression: Optional[int] = None,
+    marker_compression: Optional[int] = None,
+    k: Optional[int] = None,
+    seed: bool = True,
+    learned_ani: Optional[bool] = None,
+    median: bool = False,
+    robust: bool = False,
+    est_ci: bool = False,
+) -> Hit: ...
+def merge(
+    paths: Iterable[_Path],
+    out: _Path,
+    *,
+    duplicates: _DUPLICATES = "error",
+    format: Optional[_FORMAT] = None,
+) -> Database: ...
@@ -1,6 +1,11 @@
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
 use pyo3::types::PyString;
+use pyo3::types::PyTuple;
+use pyo3::types::PyType;
+use serde::Deserialize;
+use serde::Serialize;
 use skani::types::AniEstResult;
 
 /// A single hit found when querying a `~pyskani.Database` with a genome.
@@ -14,6 +19,25 @@ use skani::types::AniEstResult;
 ///         covered by the alignment.
 ///     reference_fraction (`float`): The fraction of the reference
 ///         sequence covered by the alignment.
+///     query_contig_name (`str`): The name of the query contig.
+///     query_contig_count (`int`): The number of contigs in the query.
+///     query_contig_length_50 (`float`): The 50th percentile of the
+///         query contig lengths.
+///     query_contig_length_90 (`float`): The 90th percentile of the
+///         query contig lengths.
+///     reference_contig_name (`str`): The name of the reference contig.
+///     reference_contig_count (`int`): The number of contigs in the
+///         reference.
+///     reference_contig_length_50 (`float`): The 50th percentile of the
+///         reference contig lengths.
+///     reference_contig_length_90 (`float`): The 90th percentile of the
+///         reference contig lengths.
+///     standard_deviation (`float`): The standard deviation of the
+///         identity estimate.
+///     ci_lower (`float`): The lower bound of the identity confidence
+///         interval, if it was estimated.
+///     ci_upper (`float`): The upper bound of the identity confidence
+///         interval, if it was estimated.
 ///
 #[pyclass(module = "pyskani._skani")]
 pub struct Hit {
@@ -23,13 +47,30 @@ pub struct Hit {
 #[pymethods]
 impl Hit {
     /// Create a new `Hit` object.
+    ///
+    /// .. versionchanged:: 0.3.0
+    ///     Added the detailed statistics and confidence interval arguments,
+    ///     with the contig names defaulting to the genome names.
+    ///
     #[new]
+    #[pyo3(signature = (identity, query_name, query_fraction, reference_name, reference_fraction, query_contig_name=None, query_contig_count=0, query_contig_length_50=0.0, query_contig_length_90=0.0, reference_contig_name=None, reference_contig_count=0, reference_contig_length_50=0.0, reference_contig_length_90=0.0, standard_deviation=0.0, ci_lower=0.0, ci_upper=0.0))]
     pub fn __init__(
         identity: f32,
         query_name: &str,
         query_fraction: f32,
         reference_name: &str,
         reference_fraction: f32,
+        query_contig_name: Option<&str>,
+        query_contig_count: usize,
+        query_contig_length_50: f32,
+        query_contig_length_90: f32,
+        reference_contig_name: Option<&str>,
+        reference_contig_count: usize,
+        reference_contig_length_50: f32,
+        reference_contig_length_90: f32,
+        standard_deviation: f32,
+        ci_lower: f32,
+        ci_upper: f32,
     ) -> PyResult<PyClassInitializer<Self>> {
         if identity < 0.0 || identity > 1.0 {
             let msg = format!("Invalid value for `identity`: {}", identity);
@@ -46,6 +87,18 @@ impl Hit {
             );
             return Err(PyValueError::new_err(msg));
         }
+        for (name, value) in [
+            ("query_contig_length_50", query_contig_length_50),
+            ("query_contig_length_90", query_contig_length_90),
+            ("reference_contig_length_50", reference_contig_length_50),
+            ("reference_contig_length_90", reference_contig_length_90),
+            ("standard_deviation", standard_deviation),
+        ] {
+            if value < 0.0 {
+                let msg = format!("Invalid value for `{}`: {}", name, value);
+                return Err(PyValueError::new_err(msg));
+            }
+        }
 
         let mut result = AniEstResult::default();
         result.ani = identity;
@@ -53,26 +106,51 @@ impl Hit {
         result.align_fraction_ref = reference_fraction;
         result.query_file = query_name.to_string();
         result.ref_file = reference_name.to_string();
+        result.query_contig = query_contig_name.unwrap_or(query_name).to_string();
+        result.ref_contig = reference_contig_name.unwrap_or(reference_name).to_string();
+        result.num_contigs_q = query_contig_count as _;
+        result.num_contigs_r = reference_contig_count as _;
+        result.quant_50_contig_len_q = query_contig_length_50;
+        result.quant_90_contig_len_q = query_contig_length_90;
+        result.quant_50_contig_len_r = reference_contig_length_50;
+        result.quant_90_contig_len_r = reference_contig_length_90;
+        result.std = standard_deviation;
+        result.ci_lower = ci_lower;
+        result.ci_upper = ci_upper;
 
         Ok(Hit::from(result).into())
     }
 
     /// Return ``repr(self)``.
     pub fn __repr__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
-        let template = PyString::new(py, "Hit(identity={!r}, query_name={!r}, query_fraction={!r}, reference_name={!r}, reference_fraction={!r})");
-        let fmt = template.call_method1(
-            pyo3::intern!(py, "format"),
-            (
-                self.get_identity(),
-                self.get_query_name(),
-                self.get_query_fraction(),
-                self.get_reference_name(),
-                self.get_reference_fraction(),
-            ),
-        );
+        let template = PyString::new(py, "Hit(identity={!r}, query_name={!r}, query_fraction={!r}, reference_name={!r}, reference_fraction={!r}, query_contig_name={!r}, query_contig_count={!r}, query_contig_length_50={!r}, query_contig_length_90={!r}, reference_contig_name={!r}, reference_contig_count={!r}, reference_contig_length_50={!r}, reference_contig_length_90={!r}, standard_deviation={!r}, ci_lower={!r}, ci_upper={!r})");
+        let fmt = template.call_method1(pyo3::intern!(py, "format"), self.args(py)?);
         fmt
     }
 
+    /// Reduce the hit for pickling.
+    pub fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyBytes>,))> {
+        let py = slf.py();
+        let state = match bincode::serialize(&HitState::from(&slf.borrow().result)) {
+            Ok(state) => state,
+            Err(err) => return Err(PyValueError::new_err(err.to_string())),
+        };
+        let from_state = slf.get_type().getattr(pyo3::intern!(py, "_from_state"))?;
+        Ok((from_state, (PyBytes::new(py, &state),)))
+    }
+
+    /// Restore a hit from its serialized state after unpickling.
+    #[classmethod]
+    #[pyo3(name = "_from_state")]
+    pub fn from_state(_cls: &Bound<'_, PyType>, state: &Bound<'_, PyBytes>) -> PyResult<Self> {
+        match bincode::deserialize::<HitState>(state.as_bytes()) {
+            Ok(state) => Ok(Self::from(AniEstResult::from(state))),
+            Err(err) => Err(PyValueError::new_err(err.to_string())),
+        }
+    }
+
     /// `float`: The average nucleotide identity between the two genomes.
     #[getter]
     pub fn get_identity(&self) -> f32 {
@@ -102,6 +180,150 @@ impl Hit {
     pub fn get_reference_fraction(&self) -> f32 {
         self.result.align_fraction_ref
     }
+
+    /// `str`: The name of the query contig.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_query_contig_name(&self) -> &str {
+        self.result.query_contig.as_str()
+    }
+
+    /// `int`: The number of contigs in the query genome.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_query_contig_count(&self) -> usize {
+        self.result.num_contigs_q as usize
+    }
+
+    /// `float`: The 50th percentile of the query contig lengths.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_query_contig_length_50(&self) -> f32 {
+        self.result.quant_50_contig_len_q
+    }
+
+    /// `float`: The 90th percentile of the query contig lengths.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_query_contig_length_90(&self) -> f32 {
+        self.result.quant_90_contig_len_q
+    }
+
+    /// `str`: The name of the reference contig.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_reference_contig_name(&self) -> &str {
+        self.result.ref_contig.as_str()
+    }
+
+    /// `int`: The number of contigs in the reference genome.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_reference_contig_count(&self) -> usize {
+        self.result.num_contigs_r as usize
+    }
+
+    /// `float`: The 50th percentile of the reference contig lengths.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_reference_contig_length_50(&self) -> f32 {
+        self.result.quant_50_contig_len_r
+    }
+
+    /// `float`: The 90th percentile of the reference contig lengths.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_reference_contig_length_90(&self) -> f32 {
+        self.result.quant_90_contig_len_r
+    }
+
+    /// `float`: The standard deviation of the identity estimate.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_standard_deviation(&self) -> f32 {
+        self.result.std
+    }
+
+    /// `float`: The lower bound of the identity confidence interval.
+    ///
+    /// The confidence interval is only estimated when querying with
+    /// ``est_ci=True``.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_ci_lower(&self) -> f32 {
+        self.result.ci_lower
+    }
+
+    /// `float`: The upper bound of the identity confidence interval.
+    ///
+    /// The confidence interval is only estimated when querying with
+    /// ``est_ci=True``.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_ci_upper(&self) -> f32 {
+        self.result.ci_upper
+    }
+}
+
+impl Hit {
+    /// Get the arguments to pass to `Hit.__init__` to recreate this hit.
+    fn args<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        PyTuple::new(
+            py,
+            [
+                self.get_identity().into_pyobject(py)?.into_any(),
+                self.get_query_name().into_pyobject(py)?.into_any(),
+                self.get_query_fraction().into_pyobject(py)?.into_any(),
+                self.get_reference_name().into_pyobject(py)?.into_any(),
+                self.get_reference_fraction().into_pyobject(py)?.into_any(),
+                self.get_query_contig_name().into_pyobject(py)?.into_any(),
+                self.get_query_contig_count().into_pyobject(py)?.into_any(),
+                self.get_query_contig_length_50()
+                    .into_pyobject(py)?
+                    .into_any(),
+                self.get_query_contig_length_90()
+                    .into_pyobject(py)?
+                    .into_any(),
+                self.get_reference_contig_name()
+                    .into_pyobject(py)?
+                    .into_any(),
+                self.get_reference_contig_count()
+                    .into_pyobject(py)?
+                    .into_any(),
+                self.get_reference_contig_length_50()
+                    .into_pyobject(py)?
+                    .into_any(),
+                self.get_reference_contig_length_90()
+                    .into_pyobject(py)?
+                    .into_any(),
+                self.get_standard_deviation().into_pyobject(py)?.into_any(),
+                self.get_ci_lower().into_pyobject(py)?.into_any(),
+                self.get_ci_upper().into_pyobject(py)?.into_any(),
+            ],
+        )
+    }
 }
 
 impl AsRef<AniEstResult> for Hit {
@@ -121,3 +343,88 @@ impl From<AniEstResult> for Hit {
         Self { result }
     }
 }
+
+/// The serialized state of a `Hit`, used for pickling.
+///
+/// `AniEstResult` does not implement `serde` traits, so its fields are
+/// mirrored here to be written with `bincode`.
+#[derive(Serialize, Deserialize)]
+struct HitState {
+    ani: f32,
+    align_fraction_query: f32,
+    align_fraction_ref: f32,
+    ref_file: String,
+    query_file: String,
+    query_contig: String,
+    ref_contig: String,
+    ci_upper: f32,
+    ci_lower: f32,
+    aai: bool,
+    quant_90_contig_len_r: f32,
+    quant_90_contig_len_q: f32,
+    quant_50_contig_len_r: f32,
+    quant_50_contig_len_q: f32,
+    quant_10_contig_len_r: f32,
+    quant_10_contig_len_q: f32,
+    std: f32,
+    num_contigs_q: u32,
+    num_contigs_r: u32,
+    avg_chain_int_len: f32,
+    total_bases_covered: u32,
+}
+
+impl From<&AniEstResult> for HitState {
+    fn from(result: &AniEstResult) -> Self {
+        Self {
+            ani: result.ani,
+            align_fraction_query: result.align_fraction_query,
+            align_fraction_ref: result.align_fraction_ref,
+            ref_file: result.ref_file.clone(),
+            query_file: result.query_file.clone(),
+            query_contig: result.query_contig.clone(),
+            ref_contig: result.ref_contig.clone(),
+            ci_upper: result.ci_upper,
+            ci_lower: result.ci_lower,
+            aai: result.aai,
+            quant_90_contig_len_r: result.quant_90_contig_len_r,
+            quant_90_contig_len_q: result.quant_90_contig_len_q,
+            quant_50_contig_len_r: result.quant_50_contig_len_r,
+            quant_50_contig_len_q: result.quant_50_contig_len_q,
+            quant_10_contig_len_r: result.quant_10_contig_len_r,
+            quant_10_contig_len_q: result.quant_10_contig_len_q,
+            std: result.std,
+            num_contigs_q: result.num_contigs_q,
+            num_contigs_r: result.num_contigs_r,
+            avg_chain_int_len: result.avg_chain_int_len,
+            total_bases_covered: result.total_bases_covered,
+        }
+    }
+}
+
+impl From<HitState> for AniEstResult {
+    fn from(state: HitState) -> Self {
+        AniEstResult {
+            ani: state.ani,
+            align_fraction_query: state.align_fraction_query,
+            align_fraction_ref: state.align_fraction_ref,
+            ref_file: state.ref_file,
+            query_file: state.query_file,
+            query_contig: state.query_contig,
+            ref_contig: state.ref_contig,
+            ci_upper: state.ci_upper,
+            ci_lower: state.ci_lower,
+            aai: state.aai,
+            quant_90_contig_len_r: state.quant_90_contig_len_r,
+            quant_90_contig_len_q: state.quant_90_contig_len_q,
+            quant_50_contig_len_r: state.quant_50_contig_len_r,
+            quant_50_contig_len_q: state.quant_50_contig_len_q,
+            quant_10_contig_len_r: state.quant_10_contig_len_r,
+            quant_10_contig_len_q: state.quant_10_contig_len_q,
+            std: state.std,
+            num_contigs_q: state.num_contigs_q,
+            num_contigs_r: state.num_contigs_r,
+            avg_chain_int_len: state.avg_chain_int_len,
+            total_bases_covered: state.total_bases_covered,
+        }
+    }
+}
@@ -1,9 +1,14 @@
 extern crate bincode;
+extern crate lru;
+extern crate memmap2;
+extern crate needletail;
 extern crate pyo3;
 extern crate pyo3_built;
+extern crate rayon;
 extern crate skani;
 
 mod hit;
+mod params;
 mod sketch;
 mod utils;
 
@@ -13,57 +18,257 @@ mod build {
 }
 
 use std::borrow::Cow;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fs::File;
 use std::io::Read;
 use std::io::Seek;
+use std::io::SeekFrom;
 use std::io::Write;
+use std::num::NonZeroUsize;
 use std::ops::Index;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
 use std::sync::RwLock;
 
+use lru::LruCache;
+use memmap2::Mmap;
+use pyo3::exceptions::PyBlockingIOError;
 use pyo3::exceptions::PyFileExistsError;
 use pyo3::exceptions::PyKeyError;
 use pyo3::exceptions::PyOSError;
 use pyo3::exceptions::PyRuntimeError;
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::pybacked::PyBackedStr;
+use pyo3::types::PyBytes;
+use pyo3::types::PyDict;
+use pyo3::types::PyIterator;
+use pyo3::types::PyList;
 use pyo3::types::PyTuple;
 use pyo3::types::PyType;
 use pyo3_built::pyo3_built;
+use rayon::prelude::*;
 use skani::params::CommandParams;
 use skani::params::SketchParams;
 use skani::sketch_db::IndexEntry;
+use skani::types::AniEstResult;
 
 use self::hit::Hit;
+use self::params::SketchParameters;
 use self::sketch::Sketch;
 
+pyo3::import_exception!(io, UnsupportedOperation);
+pyo3::create_exception!(
+    pyskani,
+    SkaniError,
+    PyRuntimeError,
+    "An error raised when ``skani`` fails on an unexpected input."
+);
+
+/// The options for comparing genomes, shared by all search methods.
+struct SearchOptions {
+    learned_ani: Option<bool>,
+    median: bool,
+    robust: bool,
+    cutoff: Option<f64>,
+    faster_small: bool,
+    est_ci: bool,
+    individual_contigs: bool,
+}
+
+impl SearchOptions {
+    /// Build the command parameters for comparing genomes with these options.
+    fn command_params(&self, params: &SketchParams) -> CommandParams {
+        // Use the regression model by default if possible, which was only
+        // trained for nucleotide identity
+        let learned_ani = self.learned_ani.unwrap_or_else(|| {
+            !params.use_aa
+                && skani::regression::use_learned_ani(
+                    params.c,
+                    self.individual_contigs,
+                    false,
+                    self.median,
+                )
+        });
+        CommandParams {
+            screen: false,
+            screen_val: self.cutoff.unwrap_or(0.0),
+            mode: skani::params::Mode::Search,
+            out_file_name: Default::default(),
+            ref_files: Default::default(),
+            query_files: Default::default(),
+            refs_are_sketch: true,
+            queries_are_sketch: true,
+            robust: self.robust,
+            median: self.median,
+            sparse: false,
+            full_matrix: false,
+            max_results: 1_000_000_000,
+            individual_contig_q: self.individual_contigs,
+            individual_contig_r: false,
+            min_aligned_frac: skani::params::D_FRAC_COVER_CUTOFF.parse::<f64>().unwrap() / 100.0,
+            keep_refs: true,
+            est_ci: self.est_ci,
+            learned_ani,
+            detailed_out: false,
+            diagonal: false,
+            distance: false,
+            rescue_small: !self.faster_small,
+            separate_sketches: false,
+            both_min_aligned_frac: -0.01,
+            short_header: false,
+        }
+    }
+}
+
+/// The policy for references found in both databases of a merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DuplicatePolicy {
+    /// Fail before copying any reference.
+    Error,
+    /// Keep the reference already in the database.
+    Skip,
+    /// Replace the reference with the one being merged.
+    Replace,
+}
+
+impl DuplicatePolicy {
+    fn parse(policy: &str) -> PyResult<Self> {
+        match policy {
+            "error" => Ok(DuplicatePolicy::Error),
+            "skip" => Ok(DuplicatePolicy::Skip),
+            "replace" => Ok(DuplicatePolicy::Replace),
+            other => Err(PyValueError::new_err(format!(
+                "invalid duplicates policy: {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// A thread-safe LRU cache of deserialized sketches.
+struct SketchCache {
+    capacity: NonZeroUsize,
+    sketches: Mutex<LruCache<String, Sketch>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl SketchCache {
+    fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            capacity,
+            sketches: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    fn get(&self, name: &str) -> PyResult<Option<Sketch>> {
+        let sketch = self
+            .sketches
+            .lock()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+            .get(name)
+            .cloned();
+        match sketch {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        Ok(sketch)
+    }
+
+    fn put(&self, name: &str, sketch: Sketch) -> PyResult<()> {
+        self.sketches
+            .lock()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+            .put(name.to_string(), sketch);
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> PyResult<()> {
+        self.sketches
+            .lock()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+            .pop(name);
+        Ok(())
+    }
+}
+
+/// The files of a database folder, besides the sketches of a separated database.
+const DATABASE_FILES: [&str; 3] = ["markers.bin", "index.db", "sketches.db"];
+
 enum DatabaseStorage {
     Memory(HashMap<String, Sketch>),
-    Folder(PathBuf),
-    Consolidated(PathBuf, HashMap<String, IndexEntry>),
+    // the sketch file paths are mapped to their reference name, since
+    // references with the same file name would share a sketch file, and
+    // the sketch files written since the last flush are synchronized then
+    Folder(
+        PathBuf,
+        HashMap<PathBuf, String>,
+        Mutex<Vec<PathBuf>>,
+        Option<SketchCache>,
+    ),
+    Consolidated(
+        PathBuf,
+        HashMap<String, IndexEntry>,
+        Option<Mmap>,
+        Option<SketchCache>,
+    ),
 }
 
 impl DatabaseStorage {
-    fn store(&mut self, sketch: Sketch, params: &SketchParams) -> PyResult<()> {
+    /// Get the path to the sketch file of a reference in a separated database.
+    ///
+    /// Like the ``skani`` CLI, only the last component of the reference
+    /// name is used, so that references named after a path are stored
+    /// directly inside the database folder.
+    fn sketch_path(folder: &Path, name: &str) -> PathBuf {
+        let file_name = Path::new(name)
+            .file_name()
+            .map(|x| x.to_string_lossy())
+            .unwrap_or(Cow::Borrowed(name));
+        folder.join(format!("{}.sketch", file_name))
+    }
+
+    fn store(&mut self, name: &str, sketch: Sketch, params: &SketchParams) -> PyResult<()> {
         match self {
             DatabaseStorage::Memory(memory) => {
-                let name = sketch.as_ref().file_name.clone();
-                memory.insert(name, sketch);
+                memory.insert(name.to_string(), sketch);
                 Ok(())
             }
-            DatabaseStorage::Folder(folder) => {
-                let sketch_path = folder.join(format!("{}.sketch", &sketch.as_ref().file_name));
-                let writer = utils::buffered_create(&sketch_path)?;
-                match bincode::serialize_into(writer, &(params, sketch.as_ref())) {
-                    Ok(()) => Ok(()),
-                    Err(err) => return Err(PyValueError::new_err(err.to_string())),
+            DatabaseStorage::Folder(folder, paths, unsynced, cache) => {
+                // ensure the sketch file of another reference is not replaced
+                let sketch_path = Self::sketch_path(folder, name);
+                if let Some(other) = paths.get(&sketch_path).filter(|other| *other != name) {
+                    return Err(PyValueError::new_err(format!(
+                        "sketch file of {:?} already used by {:?}: {}",
+                        name,
+                        other,
+                        sketch_path.display()
+                    )));
                 }
+                if let Some(cache) = cache {
+                    cache.remove(name)?;
+                }
+                utils::unsynced_write(&sketch_path, |writer| {
+                    bincode::serialize_into(writer, &(params, sketch.as_ref()))
+                        .map_err(|err| PyValueError::new_err(err.to_string()))
+                })?;
+                unsynced
+                    .get_mut()
+                    .map_err(|_| utils::poisoned_lock_error())?
+                    .push(sketch_path.clone());
+                paths.insert(sketch_path, name.to_string());
+                Ok(())
             }
-            DatabaseStorage::Consolidated(folder, index) => {
+            DatabaseStorage::Consolidated(folder, index, ..) => {
                 // ensure no duplicate names otherwise the hashmap is invalid
-                let name = &sketch.as_ref().file_name;
                 if index.contains_key(name) {
                     return Err(PyValueError::new_err(format!(
                         "duplicate name in sketches: {:?}",
@@ -75,51 +280,125 @@ impl DatabaseStorage {
                     Ok(buffer) => buffer,
                     Err(err) => return Err(PyValueError::new_err(err.to_string())),
                 };
-                // open the sketches file and create an index entry
+                // open the sketches file and create an index entry (the
+                // position of a file opened in append mode is only moved
+                // to the end on the first write, so seek explicitly)
                 let mut writer = utils::buffered_append(&folder.join("sketches.db"))?;
                 let index_entry = IndexEntry {
-                    file_name: name.clone(),
-                    offset: writer.stream_position()?,
+                    file_name: name.to_string(),
+                    offset: writer.seek(SeekFrom::End(0))?,
                     length: buffer.len() as u64,
                 };
-                // write the serialized sketches and the record the index entry
+                // write the serialized sketches and the record the index entry,
+                // which is only written once the sketches file is synchronized
+                // to disk by the next flush (an interrupted write leaves an
+                // unreferenced tail, which is removed by `Database.compact`)
                 writer.write_all(&buffer)?;
-                index.insert(name.clone(), index_entry);
+                writer.flush()?;
+                index.insert(name.to_string(), index_entry);
                 Ok(())
             }
         }
     }
 
+    fn cache(&self) -> Option<&SketchCache> {
+        match self {
+            DatabaseStorage::Memory(_) => None,
+            DatabaseStorage::Folder(.., cache) | DatabaseStorage::Consolidated(.., cache) => {
+                cache.as_ref()
+            }
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        match self {
+            DatabaseStorage::Memory(memory) => memory.contains_key(name),
+            DatabaseStorage::Folder(folder, paths, ..) => paths
+                .get(&Self::sketch_path(folder, name))
+                .is_some_and(|other| other == name),
+            DatabaseStorage::Consolidated(_, index, ..) => index.contains_key(name),
+        }
+    }
+
     fn load<'db>(&'db self, name: &str) -> PyResult<Cow<'db, Sketch>> {
+        match self.cache() {
+            None => self.read(name),
+            Some(cache) => {
+                if let Some(sketch) = cache.get(name)? {
+                    return Ok(Cow::Owned(sketch));
+                }
+                let sketch = self.read(name)?.into_owned();
+                cache.put(name, sketch.clone())?;
+                Ok(Cow::Owned(sketch))
+            }
+        }
+    }
+
+    fn read<'db>(&'db self, name: &str) -> PyResult<Cow<'db, Sketch>> {
         match self {
             DatabaseStorage::Memory(memory) => match memory.get(name) {
                 Some(sketch) => Ok(Cow::Borrowed(sketch)),
                 None => Err(PyKeyError::new_err(name.to_string())),
             },
-            DatabaseStorage::Folder(path) => {
-                let entry_path = path.join(format!("{}.sketch", name));
+            DatabaseStorage::Folder(path, ..) => {
+                let entry_path = Self::sketch_path(path, name);
                 let reader = utils::buffered_open(&entry_path)?;
                 match bincode::deserialize_from::<_, (SketchParams, skani::types::Sketch)>(reader) {
                     Err(err) => Err(PyValueError::new_err(err.to_string())),
                     Ok((_, raw_sketch)) => Ok(Cow::Owned(Sketch::from(raw_sketch))),
                 }
             }
-            DatabaseStorage::Consolidated(path, index) => {
+            DatabaseStorage::Consolidated(path, index, mmap, _) => {
                 let entry = match index.get(name) {
                     Some(entry) => entry,
                     None => return Err(PyKeyError::new_err(name.to_string())),
                 };
-                let mut reader = utils::buffered_open(&path.join("sketches.db"))?;
-                let mut buffer = vec![0; entry.length as usize];
-                reader.seek_relative(entry.offset as i64)?;
-                reader.read_exact(&mut buffer)?;
-                match bincode::deserialize::<(SketchParams, skani::types::Sketch)>(&buffer) {
+                let start = entry.offset as usize;
+                let end = start + entry.length as usize;
+                let decoded: bincode::Result<(SketchParams, skani::types::Sketch)> = match mmap {
+                    // deserialize directly from the memory-mapped file
+                    Some(mmap) if end <= mmap.len() => bincode::deserialize(&mmap[start..end]),
+                    // read sketches added after the file was mapped
+                    _ => {
+                        let mut reader = utils::buffered_open(&path.join("sketches.db"))?;
+                        let mut buffer = vec![0; entry.length as usize];
+                        reader.seek_relative(entry.offset as i64)?;
+                        reader.read_exact(&mut buffer)?;
+                        bincode::deserialize(&buffer)
+                    }
+                };
+                match decoded {
                     Err(err) => Err(PyValueError::new_err(err.to_string())),
                     Ok((_, raw_sketch)) => Ok(Cow::Owned(Sketch::from(raw_sketch))),
                 }
             }
         }
     }
+
+    fn remove(&mut self, name: &str) -> PyResult<()> {
+        if let Some(cache) = self.cache() {
+            cache.remove(name)?;
+        }
+        match self {
+            DatabaseStorage::Memory(memory) => match memory.remove(name) {
+                Some(_) => Ok(()),
+                None => Err(PyKeyError::new_err(name.to_string())),
+            },
+            DatabaseStorage::Folder(folder, paths, ..) => {
+                let sketch_path = Self::sketch_path(folder, name);
+                utils::remove_file(&sketch_path)?;
+                paths.remove(&sketch_path);
+                Ok(())
+            }
+            DatabaseStorage::Consolidated(_, index, ..) => {
+                // the sketch is left in `sketches.db` until compaction
+                match index.remove(name) {
+                    Some(_) => Ok(()),
+                    None => Err(PyKeyError::new_err(name.to_string())),
+                }
+            }
+        }
+    }
 }
 
 /// A database storing sketched genomes.
@@ -129,258 +408,39 @@ impl DatabaseStorage {
 /// always kept in memory; and genome sketches, which take more memory, but
 /// may be stored inside an external file.
 ///
+/// References are identified by the name recorded in their marker sketch,
+/// which is used verbatim when loading sketches and reported as-is in
+/// `Hit.reference_name`. For databases created with the ``skani`` CLI,
+/// this is the path to the genome file as given on the command line. In
+/// the ``separated`` format, sketch files are named after the last
+/// component of the reference name (e.g. ``genome.fna.sketch`` for
+/// ``data/genome.fna``), like in the ``skani`` CLI, while in the
+/// ``consolidated`` format the full name is recorded in ``index.db``.
+///
 #[pyclass(module = "pyskani._skani")]
 pub struct Database {
     params: SketchParams,
     markers: RwLock<Vec<Sketch>>,
     sketches: RwLock<DatabaseStorage>,
+    writable: bool,
+    // held to keep the advisory lock on the folder while the database is alive
+    #[allow(dead_code)]
+    lock: Option<File>,
 }
 
 impl Database {
-    fn _sketch<'c, C>(&self, name: String, contigs: C, seed: bool) -> PyResult<Sketch>
-    where
-        C: IntoIterator<Item = &'c [u8]>,
-    {
-        // Adapted for a single genome from `fastx_to_sketches`
-        // let mut is_valid = false;
-        let mut contig_count = 0;
-        let mut sketch = skani::types::Sketch::new(
-            self.params.marker_c,
-            self.params.c,
-            self.params.k,
-            name.clone(), // file name
-            self.params.use_aa,
-        );
-
-        for (i, contig) in contigs.into_iter().enumerate() {
-            if contig.len() >= skani::params::MIN_LENGTH_CONTIG {
-                sketch.contigs.push(format!("{}_{}", &name, i));
-                sketch
-                    .contig_lengths
-                    .push(contig.len() as skani::types::GnPosition);
-                sketch.total_sequence_length += contig.len();
-                if self.params.use_aa {
-                    unimplemented!()
-                } else {
-                    skani::seeding::fmh_seeds(
-                        &contig,
-                        &self.params,
-                        contig_count,
-                        &mut sketch,
-                        seed,
-                    );
-                }
-                contig_count += 1;
-                // is_valid = true;
-            }
-        }
-
-        // NOTE(@althonos): This was removed in skani v0.3.0.
-        // if is_valid && sketch.total_sequence_length > skani::params::REPET_KMER_THRESHOLD {
-        //     sketch.repetitive_kmers =
-        //         skani::seeding::get_repetitive_kmers(&sketch.kmer_seeds_k, sketch.c);
-        // }
-
-        Ok(Sketch::from(sketch))
-    }
-
-    fn _save_markers<P>(&self, path: P) -> PyResult<()>
-    where
-        P: AsRef<Path>,
-    {
-        let writer = utils::buffered_create(path.as_ref())?;
-        if let Ok(vec) = self.markers.read() {
-            let refs = vec.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
-            match bincode::serialize_into(writer, &(&self.params, &refs)) {
-                Ok(()) => Ok(()),
-                Err(err) => Err(PyValueError::new_err(err.to_string())),
-            }
-        } else {
-            Err(self::utils::poisoned_lock_error())
-        }
-    }
-
-    fn _save_index<P>(&self, path: P, index: &HashMap<String, IndexEntry>) -> PyResult<()>
-    where
-        P: AsRef<Path>,
-    {
-        let mut index_vec: Vec<&IndexEntry> = index.values().collect();
-        index_vec.sort_unstable_by_key(|entry| entry.offset);
-
-        let writer = utils::buffered_create(path.as_ref())?;
-        match bincode::serialize_into(writer, &index_vec) {
-            Ok(()) => Ok(()),
-            Err(err) => return Err(PyValueError::new_err(err.to_string())),
-        }
-    }
-
-    fn _flush(&self, storage: &DatabaseStorage) -> PyResult<()> {
-        match storage {
-            DatabaseStorage::Memory(_) => Ok(()),
-            DatabaseStorage::Folder(folder) => self._save_markers(folder.join("markers.bin")),
-            DatabaseStorage::Consolidated(folder, index) => {
-                self._save_markers(folder.join("markers.bin"))?;
-                self._save_index(folder.join("index.db"), index)?;
-                Ok(())
-            }
-        }
-    }
-}
-
-#[pymethods]
-impl Database {
-    /// Load a database from a folder containing sketches.
-    ///
-    /// The sketches will be loaded in memory to speed-up querying. To
-    /// reduce memory consumption and load sketches lazily from the folder,
-    /// use `Database.open`.
-    ///
-    /// Arguments:
-    ///     path (`str`, `bytes`, or `os.PathLike`): The path to the
-    ///         folder containing the sketched references.
-    ///
-    /// Returns:
-    ///     `~pyskani.Database`: A database with all sketches loaded in memory.
-    ///
-    /// Raises:
-    ///     `OSError`: When the files from the folder could not be opened.
-    ///     `ValueError`: When the sketches could not be deserialized.
-    ///
-    #[classmethod]
-    #[allow(unused)]
-    pub fn load<'py>(cls: &Bound<'py, PyType>, path: &Bound<'py, PyAny>) -> PyResult<Self> {
-        // load marker genes like in `Database.open`.
-        let mut db = Self::open(cls, path)?;
-
-        // obtain Unicode representation of path
-        let path = self::utils::fsdecode(path)?;
-
-        // load reference sketches record in marker file
-        let mut sketches = HashMap::new();
-        let mut handle = db.sketches.read().unwrap();
-        for marker in db.markers.read().unwrap().iter() {
-            let sketch = handle.load(&marker.as_ref().file_name)?;
-            let name = Path::new(&(*sketch).as_ref().file_name)
-                .file_name()
-                .unwrap()
-                .to_os_string()
-                .into_string()
-                .unwrap(); // FIXME
-            sketches.insert(name, sketch.into_owned());
-        }
-
-        drop(handle);
-        db.sketches = DatabaseStorage::Memory(sketches).into();
-        Ok(db)
-    }
-
-    /// Open a database from a folder containing sketches.
-    ///
-    /// The marker sketches will be loaded in memory, but the sketches will
-    /// be loaded only when needed when querying. To speed-up querying by
-    /// pre-fetching sketches, use `Database.load`.
-    ///
-    /// Arguments:
-    ///     path (`str`, `bytes`, or `os.PathLike`): The path to the
-    ///         folder containing the sketched references.
-    ///
-    /// Returns:
-    ///     `~pyskani.Database`: A database with only markers loaded in memory.
-    ///
-    /// Raises:
-    ///     `OSError`: When the files from the folder could not be opened.
-    ///     `ValueError`: When the markers could not be deserialized.
-    ///
-    #[classmethod]
-    #[allow(unused)]
-    pub fn open<'py>(cls: &Bound<'py, PyType>, path: &Bound<'py, PyAny>) -> PyResult<Self> {
-        // obtain Unicode representation of path
-        let decoded = self::utils::fsdecode(path)?;
-        let fspath = Path::new(decoded.to_str()?);
-
-        // load marker sketches
-        let markers_path = fspath.join("markers.bin");
-        let reader = utils::buffered_open(&markers_path)?;
-        let (params, raw_markers) =
-            match bincode::deserialize_from::<_, (SketchParams, Vec<skani::types::Sketch>)>(reader)
-            {
-                Ok((params, raw_markers)) => (params, raw_markers),
-                Err(err) => return Err(PyValueError::new_err(err.to_string())),
-            };
-        let markers = raw_markers.into_iter().map(Sketch::from).collect();
-
-        // identify whether this is a consolidated database or not
-        let index_path = fspath.join("index.db");
-        let sketches_path = fspath.join("sketches.db");
-        if index_path.exists() && sketches_path.exists() {
-            let reader = utils::buffered_open(&index_path)?;
-            let index = match bincode::deserialize_from::<_, Vec<IndexEntry>>(reader) {
-                Ok(v) => v
-                    .into_iter()
-                    .map(|entry| (entry.file_name.clone(), entry))
-                    .collect::<HashMap<_, _>>(),
-                Err(err) => return Err(PyValueError::new_err(err.to_string())),
-            };
-            Ok(Self {
-                params,
-                markers: RwLock::new(markers),
-                sketches: RwLock::new(DatabaseStorage::Consolidated(PathBuf::from(fspath), index)),
-            })
-        } else {
-            // use the folder for storage
-            Ok(Self {
-                params,
-                markers: RwLock::new(markers),
-                sketches: RwLock::new(DatabaseStorage::Folder(PathBuf::from(fspath))),
-            })
-        }
-    }
-
-    /// Create a new database.
-    ///
-    /// Arguments:
-    ///     path (`str`, `bytes`, `os.PathLike`, or `None`): The path of the
-    ///         folder to use for storing the sketches. If `None` given, the
-    ///         sketches are kept in memory. A new folder will be created if
-    ///         it does not exist.
-    ///
-    /// Keyword Arguments:
-    ///     compression (`int`): The compression factor for sketches. Memory
-    ///         usage and runtime is inversely proportional to the given
-    ///         value; lower values allows for ANI comparison of more distant
-    ///         genomes
-    ///     marker_compression (`int`): The compression factor for marker
-    ///         k-mers. Markers are used for filtering. You want at least ~100
-    ///         markers, so ``genome_size/marker_compression > 100`` is highly
-    ///         recommended. Higher value is more time/memory efficient.
-    ///     format (`str`): The database format to use. Use either
-    ///         ``separated`` to write one sketch file per sketch genome
-    ///         (as in ``skani<0.3.0``), or ``consolidated`` (the default)
-    ///         to write a single sketch file (as in ``skani>=0.3.0``)
-    ///
-    /// Raises:
-    ///     `OSError`: When a new folder could not be created.
-    ///     `FileExistsError`: When the folder already contains sketches.
-    ///
-    /// .. versionadded:: 0.2.0
-    ///     The ``format`` keyword argument.
-    ///
-    #[new]
-    #[pyo3(signature = (path=None, *, compression=125, marker_compression=1000, k=15, format=None))]
-    pub fn __init__<'py>(
+    fn new<'py>(
         path: Option<&Bound<'py, PyAny>>,
-        compression: usize,
-        marker_compression: usize,
-        k: usize,
+        params: SketchParams,
         format: Option<String>,
-    ) -> PyResult<PyClassInitializer<Self>> {
+    ) -> PyResult<Self> {
+        let mut lock = None;
         let storage = match path {
             None => DatabaseStorage::Memory(HashMap::new()),
             Some(folder) => {
-                // obtain Unicode representation of path
-                let folder = self::utils::fsdecode(folder)?;
+                // obtain the path to the folder
+                let buf = self::utils::fspath(folder)?;
                 // create the folder if it does not exist
-                let buf = PathBuf::from(folder.to_str()?);
                 if !buf.exists() {
                     if let Err(err) = std::fs::create_dir_all(&buf) {
                         return if let Some(code) = err.raw_os_error() {
@@ -391,7 +451,8 @@ impl Database {
                         };
                     }
                 }
-                // check the folder is not already in use
+                // lock the folder and check it is not already in use
+                lock = Some(utils::lock_folder(&buf)?);
                 if buf.join("markers.bin").exists() {
                     return Err(PyFileExistsError::new_err(
                         buf.join("markers.bin").display().to_string(),
@@ -402,264 +463,1899 @@ impl Database {
                     .map(String::as_str)
                     .unwrap_or("consolidated")
                 {
-                    "consolidated" => DatabaseStorage::Consolidated(buf, HashMap::new()),
-                    "separated" => DatabaseStorage::Folder(buf),
+                    "consolidated" => {
+                        DatabaseStorage::Consolidated(buf, HashMap::new(), None, None)
+                    }
+                    "separated" => {
+                        DatabaseStorage::Folder(buf, HashMap::new(), Default::default(), None)
+                    }
                     other => {
                         return Err(PyValueError::new_err(format!("invalid format: {}", other)))
                     }
                 }
             }
         };
-        let sketcher = Self {
+        Ok(Self {
             sketches: RwLock::new(storage),
             markers: Default::default(),
-            params: SketchParams::new(marker_compression, compression, k, false, false),
-        };
-        Ok(sketcher.into())
+            params,
+            writable: true,
+            lock,
+        })
     }
 
-    pub fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
-        slf
-    }
+    fn open_folder(fspath: &Path, cache_size: usize, writable: bool) -> PyResult<Self> {
+        let cache = NonZeroUsize::new(cache_size).map(SketchCache::new);
 
-    #[allow(unused_variables)]
-    pub fn __exit__<'py>(
-        &self,
-        exc_type: &Bound<'py, PyAny>,
-        exc_value: &Bound<'py, PyAny>,
-        traceback: &Bound<'py, PyAny>,
-    ) -> PyResult<bool> {
-        self.flush()?;
-        Ok(false)
-    }
+        // lock the folder before reading if the database may be modified
+        let lock = if writable {
+            Some(utils::lock_folder(fspath)?)
+        } else {
+            None
+        };
 
-    /// `pathlib.Path` or `None`: The path where sketches are stored.
-    #[getter]
-    pub fn get_path(&self, py: Python) -> PyResult<PyObject> {
-        if let Ok(sketches) = self.sketches.read() {
-            match *sketches {
-                DatabaseStorage::Memory(_) => Ok(py.None()),
-                DatabaseStorage::Folder(ref folder)
-                | DatabaseStorage::Consolidated(ref folder, _) => {
-                    let pathlib = py.import(pyo3::intern!(py, "pathlib"))?;
-                    let path = pathlib.call_method1(pyo3::intern!(py, "Path"), (folder,))?;
-                    Ok(path.into())
+        // complete or discard an interrupted replacement, which can only
+        // be done by a writer since it modifies the folder
+        if lock.is_some() {
+            utils::recover(fspath, &DATABASE_FILES, &["sketch"])?;
+        } else if utils::needs_recovery(fspath) {
+            return Err(PyBlockingIOError::new_err(format!(
+                "replacement of the database files in {} is incomplete, open it with mode='a' to recover",
+                fspath.display()
+            )));
+        }
+
+        // load marker sketches
+        let markers_path = fspath.join("markers.bin");
+        let reader = utils::buffered_open(&markers_path)?;
+        let (params, raw_markers) =
+            match bincode::deserialize_from::<_, (SketchParams, Vec<skani::types::Sketch>)>(reader)
+            {
+                Ok((params, raw_markers)) => (params, raw_markers),
+                Err(err) => return Err(PyValueError::new_err(err.to_string())),
+            };
+        let markers = raw_markers
+            .into_iter()
+            .map(Sketch::from)
+            .collect::<Vec<_>>();
+
+        // identify whether this is a consolidated database or not
+        let index_path = fspath.join("index.db");
+        let sketches_path = fspath.join("sketches.db");
+        let storage = if index_path.exists() && sketches_path.exists() {
+            let reader = utils::buffered_open(&index_path)?;
+            let index = match bincode::deserialize_from::<_, Vec<IndexEntry>>(reader) {
+                Ok(v) => v
+                    .into_iter()
+                    .map(|entry| (entry.file_name.clone(), entry))
+                    .collect::<HashMap<_, _>>(),
+                Err(err) => return Err(PyValueError::new_err(err.to_string())),
+            };
+            // only map the sketches if they were all written, otherwise
+            // read them with buffered reads which report missing data
+            let length = index
+                .values()
+                .map(|entry| entry.offset + entry.length)
+                .max()
+                .unwrap_or(0);
+            let mmap = utils::mmap_open(&sketches_path, length)?;
+            DatabaseStorage::Consolidated(PathBuf::from(fspath), index, mmap, cache)
+        } else {
+            // use the folder for storage
+            let paths = markers
+                .iter()
+                .map(|marker| {
+                    let name = &marker.as_ref().file_name;
+                    (DatabaseStorage::sketch_path(fspath, name), name.clone())
+                })
+                .collect();
+            DatabaseStorage::Folder(PathBuf::from(fspath), paths, Default::default(), cache)
+        };
+
+        Ok(Self {
+            params,
+            markers: RwLock::new(markers),
+            sketches: RwLock::new(storage),
+            writable,
+            lock,
+        })
+    }
+
+    fn _check_writable(&self) -> PyResult<()> {
+        if self.writable {
+            Ok(())
+        } else {
+            Err(UnsupportedOperation::new_err(
+                "database was opened in read-only mode",
+            ))
+        }
+    }
+
+    fn _sketch<'c, C>(&self, name: String, contigs: C, seed: bool) -> PyResult<Sketch>
+    where
+        C: IntoIterator<Item = &'c [u8]>,
+    {
+        Sketch::new(&self.params, name, contigs, seed)
+    }
+
+    fn _check_sketch(&self, sketch: &Sketch) -> PyResult<()> {
+        let raw = sketch.as_ref();
+        if raw.c != self.params.c
+            || raw.marker_c != self.params.marker_c
+            || raw.k != self.params.k
+            || raw.amino_acid != self.params.use_aa
+        {
+            return Err(PyValueError::new_err(format!(
+                "incompatible parameters for sketch {:?}: expected c={}, marker_c={}, k={}, found c={}, marker_c={}, k={}",
+                raw.file_name,
+                self.params.c,
+                self.params.marker_c,
+                self.params.k,
+                raw.c,
+                raw.marker_c,
+                raw.k,
+            )));
+        }
+        Ok(())
+    }
+
+    fn _check_params(&self, params: &SketchParams) -> PyResult<()> {
+        if params.c != self.params.c
+            || params.marker_c != self.params.marker_c
+            || params.k != self.params.k
+            || params.use_aa != self.params.use_aa
+        {
+            return Err(PyValueError::new_err(format!(
+                "incompatible database parameters: expected c={}, marker_c={}, k={}, use_aa={}, found c={}, marker_c={}, k={}, use_aa={}",
+                self.params.c,
+                self.params.marker_c,
+                self.params.k,
+                self.params.use_aa,
+                params.c,
+                params.marker_c,
+                params.k,
+                params.use_aa,
+            )));
+        }
+        Ok(())
+    }
+
+    fn _merge(&self, other: &Database, duplicates: DuplicatePolicy) -> PyResult<usize> {
+        self._check_params(&other.params)?;
+
+        // check for duplicates before copying anything
+        let names = other._names()?;
+        let existing = self._names()?.into_iter().collect::<HashSet<_>>();
+        if duplicates == DuplicatePolicy::Error {
+            if let Some(name) = names.iter().find(|name| existing.contains(*name)) {
+                return Err(PyValueError::new_err(format!(
+                    "duplicate reference name: {:?}",
+                    name
+                )));
+            }
+        }
+
+        // copy sketches under their storage key
+        let mut copied = 0;
+        for name in names {
+            if existing.contains(&name) {
+                if duplicates == DuplicatePolicy::Skip {
+                    continue;
+                }
+                let mut markers = self
+                    .markers
+                    .write()
+                    .map_err(|_| self::utils::poisoned_lock_error())?;
+                self.sketches
+                    .write()
+                    .map_err(|_| self::utils::poisoned_lock_error())?
+                    .remove(&name)?;
+                markers.retain(|marker| marker.as_ref().file_name != name);
+            }
+            let sketch = other
+                .sketches
+                .read()
+                .map_err(|_| self::utils::poisoned_lock_error())?
+                .load(&name)?
+                .into_owned();
+            self._add(name, sketch)?;
+            copied += 1;
+        }
+
+        self.flush()?;
+        Ok(copied)
+    }
+
+    fn _add(&self, name: String, sketch: Sketch) -> PyResult<()> {
+        // the marker name is used as the storage key, which may differ
+        // from the sketch name for individual contig references
+        let mut marker = skani::types::Sketch::get_markers_only(sketch.as_ref());
+        marker.file_name = name;
+        self.sketches
+            .write()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+            .store(&marker.file_name, sketch, &self.params)?;
+        self.markers
+            .write()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+            .push(marker.into());
+        Ok(())
+    }
+
+    fn _search(
+        &self,
+        queries: &[Sketch],
+        command_params: &CommandParams,
+    ) -> PyResult<Vec<Vec<Hit>>> {
+        // Get cutoff for screening
+        let screen_val = if command_params.screen_val != 0.0 {
+            command_params.screen_val
+        } else if self.params.use_aa {
+            skani::params::SEARCH_AAI_CUTOFF_DEFAULT
+        } else {
+            skani::params::SEARCH_ANI_CUTOFF_DEFAULT
+        };
+        // Load regression model if requested
+        let model_opt = skani::regression::get_model(self.params.c, command_params.learned_ani);
+        // Search marker sketches first
+        let markers = self
+            .markers
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        let shortlists = queries
+            .par_iter()
+            .map(|query| {
+                let genome = &query.as_ref().file_name;
+                self::utils::catch_panic("screening", format_args!("{:?}", genome), || {
+                    markers
+                        .par_iter()
+                        .enumerate()
+                        .filter(|(_, marker)| {
+                            skani::screen::check_markers_quickly(
+                                query.as_ref(),
+                                marker.as_ref(),
+                                screen_val,
+                                command_params.rescue_small,
+                            )
+                        })
+                        .map(|(i, _)| i)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        // Group the queries by shortlisted reference, in marker order
+        let mut candidates = vec![Vec::new(); markers.len()];
+        for (i, shortlist) in shortlists.into_iter().enumerate() {
+            for j in shortlist {
+                candidates[j].push(i);
+            }
+        }
+        let mut references: Vec<(String, Vec<usize>)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (marker, indices) in markers.iter().zip(candidates) {
+            if indices.is_empty() {
+                continue;
+            }
+            let name = marker.as_ref().file_name.clone();
+            match positions.get(&name) {
+                Some(&k) => {
+                    let merged = &mut references[k].1;
+                    merged.extend(indices);
+                    merged.sort_unstable();
+                    merged.dedup();
                 }
+                None => {
+                    positions.insert(name.clone(), references.len());
+                    references.push((name, indices));
+                }
+            }
+        }
+        drop(markers);
+        // Load each shortlisted reference once and chain it with its queries
+        let storage = self
+            .sketches
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        let results = references
+            .par_iter()
+            .map(|(name, indices)| -> PyResult<Vec<(usize, Hit)>> {
+                let reference = storage.load(name)?;
+                let results = indices
+                    .par_iter()
+                    .map(|&i| {
+                        let query = &queries[i].as_ref().file_name;
+                        let genomes = format_args!("{:?} against {:?}", query, name);
+                        let ani_res = self::utils::catch_panic("comparing", genomes, || {
+                            let map_params = skani::chain::map_params_from_sketch(
+                                (*reference).as_ref(),
+                                self.params.use_aa,
+                                command_params,
+                                &model_opt,
+                            );
+                            skani::chain::chain_seeds(
+                                (*reference).as_ref(),
+                                queries[i].as_ref(),
+                                map_params,
+                            )
+                        })?;
+                        Ok((i, ani_res))
+                    })
+                    .collect::<PyResult<Vec<_>>>()?;
+                let hits = results
+                    .into_iter()
+                    .filter(|(_, ani_res)| ani_res.ani > 0.1)
+                    .map(|(i, ani_res)| (i, Hit::from(ani_res)))
+                    .collect();
+                Ok(hits)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        // Group hits by query
+        let mut hits = (0..queries.len()).map(|_| Vec::new()).collect::<Vec<_>>();
+        for (i, hit) in results.into_iter().flatten() {
+            hits[i].push(hit);
+        }
+        Ok(hits)
+    }
+
+    fn _query<'c, C>(
+        &self,
+        name: String,
+        contigs: C,
+        seed: bool,
+        command_params: &CommandParams,
+        individual_contigs: bool,
+    ) -> PyResult<Vec<Hit>>
+    where
+        C: IntoIterator<Item = (String, &'c [u8])>,
+    {
+        if individual_contigs {
+            // Sketch each contig separately, skipping short contigs
+            let queries = contigs
+                .into_iter()
+                .filter(|(_, contig)| contig.len() >= skani::params::MIN_LENGTH_CONTIG)
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(|(contig_name, contig)| {
+                    // name each query after its contig, like `skani search --qi`
+                    Sketch::from_named_contigs(
+                        &self.params,
+                        contig_name.clone(),
+                        [(contig_name, contig)],
+                        seed,
+                    )
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            // Search the database with every contig
+            let hits = self._search(&queries, command_params)?;
+            Ok(hits.into_iter().flatten().collect())
+        } else {
+            // Sketch query
+            let query = Sketch::from_named_contigs(&self.params, name, contigs, seed)?;
+            // Search the database
+            let mut hits = self._search(std::slice::from_ref(&query), command_params)?;
+            Ok(hits.pop().unwrap_or_default())
+        }
+    }
+
+    fn _folder(&self) -> PyResult<Option<PathBuf>> {
+        match *self
+            .sketches
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+        {
+            DatabaseStorage::Memory(_) => Ok(None),
+            DatabaseStorage::Folder(ref folder, ..)
+            | DatabaseStorage::Consolidated(ref folder, ..) => Ok(Some(folder.clone())),
+        }
+    }
+
+    fn _names(&self) -> PyResult<Vec<String>> {
+        Ok(self
+            .markers
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+            .iter()
+            .map(|marker| marker.as_ref().file_name.clone())
+            .collect())
+    }
+
+    fn _get(&self, name: &str) -> PyResult<Option<Sketch>> {
+        let storage = self
+            .sketches
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        if !storage.contains(name) {
+            return Ok(None);
+        }
+        Ok(Some(storage.load(name)?.into_owned()))
+    }
+
+    fn _verify(&self, saved: &Self) -> PyResult<()> {
+        let error = |msg: String| {
+            PyValueError::new_err(format!("failed to verify saved database: {}", msg))
+        };
+        // check parameters
+        if saved.params.c != self.params.c
+            || saved.params.marker_c != self.params.marker_c
+            || saved.params.k != self.params.k
+            || saved.params.use_aa != self.params.use_aa
+        {
+            return Err(error("parameters differ".into()));
+        }
+        // check reference names and sketches
+        let markers = self
+            .markers
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        let saved_markers = saved
+            .markers
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        if markers.len() != saved_markers.len() {
+            return Err(error(format!(
+                "expected {} references, found {}",
+                markers.len(),
+                saved_markers.len()
+            )));
+        }
+        let storage = saved
+            .sketches
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        for (marker, saved_marker) in markers.iter().zip(saved_markers.iter()) {
+            let name = &marker.as_ref().file_name;
+            if name != &saved_marker.as_ref().file_name {
+                return Err(error(format!("missing reference {:?}", name)));
+            }
+            let sketch = storage.load(name)?;
+            let raw = (*sketch).as_ref();
+            if raw.c != self.params.c
+                || raw.k != self.params.k
+                || raw.marker_c != self.params.marker_c
+                || raw.amino_acid != self.params.use_aa
+            {
+                return Err(error(format!("invalid sketch for reference {:?}", name)));
             }
+        }
+        Ok(())
+    }
+
+    fn _write_markers<W>(&self, writer: W) -> PyResult<()>
+    where
+        W: Write,
+    {
+        if let Ok(vec) = self.markers.read() {
+            let refs = vec.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+            bincode::serialize_into(writer, &(&self.params, &refs))
+                .map_err(|err| PyValueError::new_err(err.to_string()))
         } else {
             Err(self::utils::poisoned_lock_error())
         }
     }
 
-    /// `int`: The compression factor for sketches.
-    #[getter]
-    pub fn get_compression<'py>(slf: PyRef<'py, Self>, _py: Python) -> usize {
-        slf.params.c
+    fn _write_index<W>(&self, writer: W, index: &HashMap<String, IndexEntry>) -> PyResult<()>
+    where
+        W: Write,
+    {
+        let mut index_vec: Vec<&IndexEntry> = index.values().collect();
+        index_vec.sort_unstable_by_key(|entry| entry.offset);
+        match bincode::serialize_into(writer, &index_vec) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(PyValueError::new_err(err.to_string())),
+        }
     }
 
-    /// `int`: The compression factor for marker k-mers.
-    #[getter]
-    pub fn get_marker_compression<'py>(slf: PyRef<'py, Self>, _py: Python) -> usize {
-        slf.params.marker_c
+    fn _flush(&self, storage: &DatabaseStorage) -> PyResult<()> {
+        match storage {
+            DatabaseStorage::Memory(_) => Ok(()),
+            DatabaseStorage::Folder(folder, _, unsynced, _) => {
+                // sketch files are written and removed immediately, so a
+                // reference removed since the last flush is still listed
+                // in `markers.bin` until then, but new sketch files must
+                // be on disk before they are listed
+                let mut unsynced = unsynced.lock().map_err(|_| utils::poisoned_lock_error())?;
+                utils::sync_files(
+                    folder,
+                    unsynced
+                        .iter()
+                        .map(PathBuf::as_path)
+                        .filter(|path| path.exists()),
+                )?;
+                unsynced.clear();
+                let markers_path = folder.join("markers.bin");
+                utils::atomic_write(&markers_path, |writer| self._write_markers(writer))
+            }
+            DatabaseStorage::Consolidated(folder, index, ..) => {
+                // synchronize the sketches appended since the last flush
+                // before the index referencing them is written
+                let sketches_path = folder.join("sketches.db");
+                if sketches_path.exists() {
+                    utils::sync_files(folder, [sketches_path.as_path()])?;
+                }
+                // replace the index first and the markers last, through a
+                // journal so that an interrupted flush is completed when
+                // the database is opened again
+                let index_path = folder.join("index.db");
+                let markers_path = folder.join("markers.bin");
+                utils::write_temporary(&index_path, |writer| self._write_index(writer, index))?;
+                if let Err(err) =
+                    utils::write_temporary(&markers_path, |writer| self._write_markers(writer))
+                {
+                    let _ = std::fs::remove_file(utils::temporary_path(&index_path));
+                    return Err(err);
+                }
+                utils::replace_all(folder, &["index.db", "markers.bin"], &[])
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl Database {
+    /// Load a database from a folder containing sketches.
+    ///
+    /// The sketches will be loaded in memory to speed-up querying. To
+    /// reduce memory consumption and load sketches lazily from the folder,
+    /// use `Database.open`.
+    ///
+    /// Arguments:
+    ///     path (`str`, `bytes`, or `os.PathLike`): The path to the
+    ///         folder containing the sketched references.
+    ///
+    /// Returns:
+    ///     `~pyskani.Database`: A database with all sketches loaded in memory.
+    ///
+    /// Raises:
+    ///     `OSError`: When the files from the folder could not be opened.
+    ///     `ValueError`: When the sketches could not be deserialized.
+    ///
+    #[classmethod]
+    #[allow(unused)]
+    pub fn load<'py>(cls: &Bound<'py, PyType>, path: &Bound<'py, PyAny>) -> PyResult<Self> {
+        // load marker genes like in `Database.open`.
+        let mut db = Self::open(cls, path, "r", 0)?;
+
+        // load reference sketches record in marker file
+        let mut sketches = HashMap::new();
+        let handle = db
+            .sketches
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        let markers = db
+            .markers
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        for marker in markers.iter() {
+            let name = &marker.as_ref().file_name;
+            let sketch = handle.load(name)?;
+            sketches.insert(name.clone(), sketch.into_owned());
+        }
+
+        drop(markers);
+        drop(handle);
+        db.sketches = DatabaseStorage::Memory(sketches).into();
+        db.writable = true;
+        Ok(db)
+    }
+
+    /// Open a database from a folder containing sketches.
+    ///
+    /// The marker sketches will be loaded in memory, but the sketches will
+    /// be loaded only when needed when querying. To speed-up querying by
+    /// pre-fetching sketches, use `Database.load`.
+    ///
+    /// For databases in the ``consolidated`` format, the sketches file is
+    /// memory-mapped, so that sketches are deserialized directly from
+    /// memory, and the pages can be shared between processes opening the
+    /// same database.
+    ///
+    /// Warning:
+    ///     The ``sketches.db`` file of a consolidated database must not be
+    ///     truncated or modified in place while the database is open, as
+    ///     reading from a truncated memory map crashes the interpreter.
+    ///     This never happens with the files written by ``pyskani`` or
+    ///     ``skani``, which only append sketches to this file, or replace
+    ///     it with a new file.
+    ///
+    /// Arguments:
+    ///     path (`str`, `bytes`, or `os.PathLike`): The path to the
+    ///         folder containing the sketched references.
+    ///     mode (`str`): The mode to open the database with. Use ``a`` (the
+    ///         default) to allow adding or removing sketches, or ``r`` to
+    ///         open the database in read-only mode. A database opened in
+    ///         ``a`` mode holds an exclusive lock on the folder until it is
+    ///         closed with `Database.close`, so that no other process can
+    ///         open it for writing at the same time.
+    ///     cache_size (`int`): The maximum number of sketches to keep in
+    ///         memory after they have been loaded, discarding the least
+    ///         recently used sketches first. Pass ``0`` (the default) to
+    ///         disable caching. Use `Database.cache_info` to get the cache
+    ///         statistics.
+    ///
+    /// Returns:
+    ///     `~pyskani.Database`: A database with only markers loaded in memory.
+    ///
+    /// Raises:
+    ///     `OSError`: When the files from the folder could not be opened.
+    ///     `ValueError`: When the markers could not be deserialized, or
+    ///         when the mode is invalid.
+    ///     `BlockingIOError`: When opening the database in ``a`` mode
+    ///         while it is locked by another writer, or in ``r`` mode
+    ///         while the replacement of the database files was interrupted
+    ///         or is in progress. Open the database in ``a`` mode to
+    ///         complete an interrupted replacement.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///     The ``mode`` and ``cache_size`` arguments.
+    ///
+    #[classmethod]
+    #[allow(unused)]
+    #[pyo3(signature = (path, mode="a", cache_size=0))]
+    pub fn open<'py>(
+        cls: &Bound<'py, PyType>,
+        path: &Bound<'py, PyAny>,
+        mode: &str,
+        cache_size: usize,
+    ) -> PyResult<Self> {
+        // obtain the path to the folder
+        let fspath = self::utils::fspath(path)?;
+
+        let writable = match mode {
+            "r" => false,
+            "a" => true,
+            other => return Err(PyValueError::new_err(format!("invalid mode: {:?}", other))),
+        };
+        Self::open_folder(&fspath, cache_size, writable)
+    }
+
+    /// Create a new database from a list of genome files.
+    ///
+    /// This is the equivalent of ``skani sketch -l``: the files are read
+    /// and sketched in parallel, and each sketch is written to the
+    /// database as soon as it is ready, in the order the files were given.
+    /// Each file may be in FASTA or FASTQ format, possibly compressed, and
+    /// is named after the file name without its extension, as in
+    /// `Database.sketch_file`.
+    ///
+    /// Arguments:
+    ///     paths (iterable of `str`, `bytes`, or `os.PathLike`): The paths
+    ///         to the sequence files of the reference genomes.
+    ///     path (`str`, `bytes`, `os.PathLike`, or `None`): The path of the
+    ///         folder to use for storing the sketches. If `None` given, the
+    ///         sketches are kept in memory.
+    ///
+    /// Keyword Arguments:
+    ///     parameters (`~pyskani.SketchParameters`): The parameters to
+    ///         use for sketching. Cannot be combined with the
+    ///         ``compression``, ``marker_compression`` and ``k`` arguments.
+    ///     compression (`int`): The compression factor for sketches.
+    ///     marker_compression (`int`): The compression factor for marker
+    ///         k-mers.
+    ///     k (`int`): The k-mer size to use for sketching.
+    ///     format (`str`): The database format to use, either
+    ///         ``separated`` or ``consolidated`` (the default).
+    ///     duplicates (`str`): The policy for files with the same genome
+    ///         name: ``error`` (the default) to fail before sketching
+    ///         anything, ``skip`` to keep the first file, or ``replace``
+    ///         to keep the last file.
+    ///     seed (`bool`): Compute seed positions while sketching.
+    ///     threads (`int`): The number of threads to use for reading and
+    ///         sketching the files. Pass ``0`` (the default) to use the
+    ///         default thread pool, with one thread per CPU.
+    ///
+    /// Returns:
+    ///     `~pyskani.Database`: A database containing the sketches of all
+    ///     the given genomes, flushed to disk if ``path`` was given.
+    ///
+    /// Raises:
+    ///     `OSError`: When a file could not be opened, or when a new folder
+    ///         could not be created.
+    ///     `ValueError`: When a file could not be parsed, when the
+    ///         sketching parameters or the policy are invalid, or when
+    ///         several files have the same genome name with the ``error``
+    ///         policy.
+    ///     `FileExistsError`: When the folder already contains sketches.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[classmethod]
+    #[allow(unused)]
+    #[pyo3(signature = (paths, path=None, *, parameters=None, compression=None, marker_compression=None, k=None, format=None, duplicates="error", seed=true, threads=0))]
+    pub fn from_files<'py>(
+        cls: &Bound<'py, PyType>,
+        paths: &Bound<'py, PyAny>,
+        path: Option<&Bound<'py, PyAny>>,
+        parameters: Option<PyRef<'py, SketchParameters>>,
+        compression: Option<usize>,
+        marker_compression: Option<usize>,
+        k: Option<usize>,
+        format: Option<String>,
+        duplicates: &str,
+        seed: bool,
+        threads: usize,
+    ) -> PyResult<Self> {
+        // obtain the paths to the genome files
+        let files = paths
+            .try_iter()?
+            .map(|item| self::utils::fspath(&item?))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        // select the file to sketch for each genome name before creating
+        // the database, so that duplicates do not leave a partial database
+        let policy = DuplicatePolicy::parse(duplicates)?;
+        let mut selected = HashMap::new();
+        for (i, file) in files.iter().enumerate() {
+            match selected.entry(self::utils::genome_name(file)) {
+                Entry::Vacant(entry) => {
+                    entry.insert(i);
+                }
+                Entry::Occupied(mut entry) => match policy {
+                    DuplicatePolicy::Error => {
+                        return Err(PyValueError::new_err(format!(
+                            "duplicate reference name: {:?}",
+                            entry.key()
+                        )))
+                    }
+                    DuplicatePolicy::Skip => {}
+                    DuplicatePolicy::Replace => {
+                        entry.insert(i);
+                    }
+                },
+            }
+        }
+        let mut genomes = selected
+            .into_iter()
+            .map(|(name, i)| (i, name))
+            .collect::<Vec<_>>();
+        genomes.sort_unstable();
+
+        // create the database
+        let params =
+            SketchParameters::resolve(parameters.as_deref(), compression, marker_compression, k)?;
+        let db = Self::new(path, params, format)?;
+
+        // Release the GIL while sketching and storing the genomes
+        let py = cls.py();
+        py.allow_threads(|| {
+            // sketch the genomes in parallel and send them back to this
+            // thread, which stores each sketch once all the previous ones
+            // were stored, so that only out-of-order sketches are kept
+            let (sender, receiver) = std::sync::mpsc::channel();
+            std::thread::scope(|scope| {
+                let (genomes, files, db) = (&genomes, &files, &db);
+                let worker = scope.spawn(move || {
+                    self::utils::install(threads, || {
+                        genomes.par_iter().enumerate().try_for_each_with(
+                            sender,
+                            |sender, (rank, (i, name))| {
+                                let records = self::utils::read_fastx(&files[*i])?;
+                                let contigs =
+                                    records.iter().map(|(id, seq)| (id.clone(), seq.as_slice()));
+                                let sketch = Sketch::from_named_contigs(
+                                    &db.params,
+                                    name.clone(),
+                                    contigs,
+                                    seed,
+                                )?;
+                                // the receiver is only dropped when storing failed
+                                sender.send((rank, sketch)).map_err(|_| {
+                                    PyRuntimeError::new_err("failed to store sketches")
+                                })
+                            },
+                        )
+                    })
+                });
+                let mut pending = HashMap::new();
+                let mut next = 0;
+                for (rank, sketch) in receiver {
+                    pending.insert(rank, sketch);
+                    while let Some(sketch) = pending.remove(&next) {
+                        db._add(genomes[next].1.clone(), sketch)?;
+                        next += 1;
+                    }
+                }
+                match worker.join() {
+                    Ok(result) => result?,
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            })?;
+            db.flush()
+        })?;
+
+        Ok(db)
+    }
+
+    /// Create a new database.
+    ///
+    /// Arguments:
+    ///     path (`str`, `bytes`, `os.PathLike`, or `None`): The path of the
+    ///         folder to use for storing the sketches. If `None` given, the
+    ///         sketches are kept in memory. A new folder will be created if
+    ///         it does not exist.
+    ///
+    /// Keyword Arguments:
+    ///     parameters (`~pyskani.SketchParameters`): The parameters to
+    ///         use for sketching. Cannot be combined with the
+    ///         ``compression``, ``marker_compression`` and ``k`` arguments.
+    ///     compression (`int`): The compression factor for sketches. Memory
+    ///         usage and runtime is inversely proportional to the given
+    ///         value; lower values allows for ANI comparison of more distant
+    ///         genomes
+    ///     marker_compression (`int`): The compression factor for marker
+    ///         k-mers. Markers are used for filtering. You want at least ~100
+    ///         markers, so ``genome_size/marker_compression > 100`` is highly
+    ///         recommended. Higher value is more time/memory efficient.
+    ///     k (`int`): The k-mer size to use for sketching.
+    ///     format (`str`): The database format to use. Use either
+    ///         ``separated`` to write one sketch file per sketch genome
+    ///         (as in ``skani<0.3.0``), or ``consolidated`` (the default)
+    ///         to write a single sketch file (as in ``skani>=0.3.0``)
+    ///
+    /// Raises:
+    ///     `OSError`: When a new folder could not be created.
+    ///     `FileExistsError`: When the folder already contains sketches.
+    ///     `ValueError`: When the sketching parameters are invalid.
+    ///
+    /// .. versionadded:: 0.2.0
+    ///     The ``format`` keyword argument.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///     The ``parameters`` keyword argument.
+    ///
+    #[new]
+    #[pyo3(signature = (path=None, *, parameters=None, compression=None, marker_compression=None, k=None, format=None))]
+    pub fn __init__<'py>(
+        path: Option<&Bound<'py, PyAny>>,
+        parameters: Option<PyRef<'py, SketchParameters>>,
+        compression: Option<usize>,
+        marker_compression: Option<usize>,
+        k: Option<usize>,
+        format: Option<String>,
+    ) -> PyResult<PyClassInitializer<Self>> {
+        let params =
+            SketchParameters::resolve(parameters.as_deref(), compression, marker_compression, k)?;
+        Self::new(path, params, format).map(Into::into)
+    }
+
+    pub fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    #[allow(unused_variables)]
+    pub fn __exit__<'py>(
+        &mut self,
+        exc_type: &Bound<'py, PyAny>,
+        exc_value: &Bound<'py, PyAny>,
+        traceback: &Bound<'py, PyAny>,
+    ) -> PyResult<bool> {
+        self.close()?;
+        Ok(false)
+    }
+
+    /// Reduce the database for pickling.
+    ///
+    /// Databases stored in memory are copied entirely, while only the
+    /// path of databases stored in a folder is pickled, so that they can
+    /// be opened again with `Database.open` in read-only mode. Pickling
+    /// does not flush the database, so changes must be flushed first to
+    /// be visible to the unpickled database.
+    ///
+    pub fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyTuple>> {
+        let py = slf.py();
+        let db = slf.borrow();
+        let storage = db
+            .sketches
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        let folder = match &*storage {
+            DatabaseStorage::Memory(memory) => {
+                let markers = db
+                    .markers
+                    .read()
+                    .map_err(|_| self::utils::poisoned_lock_error())?;
+                let raw_markers = markers.iter().map(|m| m.as_ref()).collect::<Vec<_>>();
+                let raw_sketches = memory
+                    .iter()
+                    .map(|(name, sketch)| (name, sketch.as_ref()))
+                    .collect::<Vec<_>>();
+                let state = match bincode::serialize(&(&db.params, &raw_markers, &raw_sketches)) {
+                    Ok(state) => state,
+                    Err(err) => return Err(PyValueError::new_err(err.to_string())),
+                };
+                let args = (slf.get_type(), (), PyBytes::new(py, &state));
+                return Ok(args.into_pyobject(py)?);
+            }
+            DatabaseStorage::Folder(folder, ..) | DatabaseStorage::Consolidated(folder, ..) => {
+                folder.clone()
+            }
+        };
+        let cache_size = storage
+            .cache()
+            .map(|cache| cache.capacity.get())
+            .unwrap_or(0);
+        let open = slf.get_type().getattr(pyo3::intern!(py, "open"))?;
+        Ok((open, (folder, "r", cache_size)).into_pyobject(py)?)
+    }
+
+    /// Restore the state of an in-memory database after unpickling.
+    pub fn __setstate__(&mut self, state: &Bound<'_, PyBytes>) -> PyResult<()> {
+        // only restore the state of a new empty database
+        let is_empty = match &*self
+            .sketches
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+        {
+            DatabaseStorage::Memory(memory) => memory.is_empty(),
+            _ => false,
+        };
+        if !is_empty || !self._names()?.is_empty() || self.lock.is_some() {
+            return Err(PyValueError::new_err(
+                "cannot restore the state of a database which already has sketches",
+            ));
+        }
+        let (params, raw_markers, raw_sketches) = match bincode::deserialize::<(
+            SketchParams,
+            Vec<skani::types::Sketch>,
+            Vec<(String, skani::types::Sketch)>,
+        )>(state.as_bytes())
+        {
+            Ok(state) => state,
+            Err(err) => return Err(PyValueError::new_err(err.to_string())),
+        };
+        let sketches = raw_sketches
+            .into_iter()
+            .map(|(name, sketch)| (name, Sketch::from(sketch)))
+            .collect();
+        self.params = params;
+        self.markers = RwLock::new(raw_markers.into_iter().map(Sketch::from).collect());
+        self.sketches = RwLock::new(DatabaseStorage::Memory(sketches));
+        Ok(())
+    }
+
+    /// `pathlib.Path` or `None`: The path where sketches are stored.
+    #[getter]
+    pub fn get_path(&self, py: Python) -> PyResult<PyObject> {
+        if let Ok(sketches) = self.sketches.read() {
+            match *sketches {
+                DatabaseStorage::Memory(_) => Ok(py.None()),
+                DatabaseStorage::Folder(ref folder, ..)
+                | DatabaseStorage::Consolidated(ref folder, ..) => {
+                    let pathlib = py.import(pyo3::intern!(py, "pathlib"))?;
+                    let path = pathlib.call_method1(pyo3::intern!(py, "Path"), (folder,))?;
+                    Ok(path.into())
+                }
+            }
+        } else {
+            Err(self::utils::poisoned_lock_error())
+        }
+    }
+
+    /// `int`: The compression factor for sketches.
+    #[getter]
+    pub fn get_compression<'py>(slf: PyRef<'py, Self>, _py: Python) -> usize {
+        slf.params.c
+    }
+
+    /// `int`: The compression factor for marker k-mers.
+    #[getter]
+    pub fn get_marker_compression<'py>(slf: PyRef<'py, Self>, _py: Python) -> usize {
+        slf.params.marker_c
+    }
+
+    /// `~pyskani.SketchParameters`: The parameters used for sketching.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    pub fn get_parameters(&self) -> SketchParameters {
+        SketchParameters::from(self.params.clone())
+    }
+
+    /// Add a reference genome to the database.
+    ///
+    /// Arguments:
+    ///     name (`str`): The name of the reference genome to add.
+    ///     contigs (`str`, `bytes`, `bytearray` or `memoryview`): The contigs
+    ///         of the reference genome.
+    ///
+    /// Keyword Arguments:
+    ///     seed (`bool`): Compute seed positions while sketching
+    ///         the query.
+    ///
+    /// Returns:
+    ///     `~pyskani.Sketch`: The sketch of the reference genome that was
+    ///     stored in the database.
+    ///
+    /// .. versionchanged:: 0.3.0
+    ///     Return the `~pyskani.Sketch` stored in the database.
+    ///
+    #[pyo3(signature = (name, *contigs, seed=true))]
+    pub fn sketch<'py>(
+        &mut self,
+        name: String,
+        contigs: &Bound<'py, PyTuple>,
+        seed: bool,
+    ) -> PyResult<Sketch> {
+        self._check_writable()?;
+        // Get a view on the contigs
+        let contents = contigs
+            .into_iter()
+            .map(|item| self::utils::Text::new(item.as_borrowed()))
+            .collect::<PyResult<Vec<_>>>()?;
+        let views = contents.iter().map(|text| text.as_bytes());
+
+        // Release the GIL while sketching
+        let py = contigs.py();
+        py.allow_threads(|| {
+            let sketch = self._sketch(name.clone(), views, seed)?;
+            self._add(name, sketch.clone())?;
+            Ok(sketch)
+        })
+    }
+
+    /// Add each contig of a reference genome to the database separately.
+    ///
+    /// Every contig long enough to be sketched is stored as its own
+    /// reference, named after the genome and the index of the contig
+    /// among the stored contigs (e.g. ``genome_0``, ``genome_1``, ...).
+    /// The parent genome name is kept in the stored sketches, so that hits
+    /// against these references report the genome in `Hit.reference_name`
+    /// and the contig in `Hit.reference_contig_name`, allowing hits to be
+    /// grouped per genome. This is equivalent to the ``--ri`` flag of the
+    /// ``skani`` CLI.
+    ///
+    /// Arguments:
+    ///     name (`str`): The name of the reference genome to add.
+    ///     contigs (`str`, `bytes`, `bytearray` or `memoryview`): The contigs
+    ///         of the reference genome.
+    ///
+    /// Keyword Arguments:
+    ///     seed (`bool`): Compute seed positions while sketching
+    ///         the query.
+    ///
+    /// Returns:
+    ///     `list` of `~pyskani.Sketch`: The sketches of the contigs that
+    ///     were stored in the database, named after the reference they
+    ///     were stored as.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[pyo3(signature = (name, *contigs, seed=true))]
+    pub fn sketch_contigs<'py>(
+        &mut self,
+        name: String,
+        contigs: &Bound<'py, PyTuple>,
+        seed: bool,
+    ) -> PyResult<Vec<Sketch>> {
+        self._check_writable()?;
+        // Get a view on the contigs
+        let contents = contigs
+            .into_iter()
+            .map(|item| self::utils::Text::new(item.as_borrowed()))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        // Release the GIL while sketching
+        let py = contigs.py();
+        py.allow_threads(|| {
+            let mut sketches = Vec::new();
+            for text in contents.iter() {
+                let contig = text.as_bytes();
+                if contig.len() < skani::params::MIN_LENGTH_CONTIG {
+                    continue;
+                }
+                let contig_name = format!("{}_{}", &name, sketches.len());
+                let sketch = Sketch::from_named_contigs(
+                    &self.params,
+                    name.clone(),
+                    [(contig_name.clone(), contig)],
+                    seed,
+                )?;
+                sketches.push(sketch.renamed(contig_name.clone()));
+                self._add(contig_name, sketch)?;
+            }
+            Ok(sketches)
+        })
+    }
+
+    /// Add a reference genome to the database from a sequence file.
+    ///
+    /// The file may be in FASTA or FASTQ format, and may be compressed
+    /// with ``gzip``, ``bzip2`` or ``xz``. It is read and parsed directly
+    /// in Rust, without building intermediate Python objects. Every
+    /// record of the file is sketched as a contig of the genome.
+    ///
+    /// Arguments:
+    ///     path (`str`, `bytes`, or `os.PathLike`): The path to the
+    ///         sequence file of the reference genome.
+    ///     name (`str` or `None`): The name of the reference genome to
+    ///         add. By default, the file name is used without its
+    ///         extension (e.g. ``genome`` for ``genome.fna.gz``).
+    ///
+    /// Keyword Arguments:
+    ///     seed (`bool`): Compute seed positions while sketching
+    ///         the query.
+    ///
+    /// Returns:
+    ///     `~pyskani.Sketch`: The sketch of the reference genome that was
+    ///     stored in the database.
+    ///
+    /// Raises:
+    ///     `OSError`: When the file could not be opened or read.
+    ///     `ValueError`: When the file could not be parsed.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[pyo3(signature = (path, name=None, *, seed=true))]
+    pub fn sketch_file<'py>(
+        &mut self,
+        path: &Bound<'py, PyAny>,
+        name: Option<String>,
+        seed: bool,
+    ) -> PyResult<Sketch> {
+        self._check_writable()?;
+        let py = path.py();
+        let path = self::utils::fspath(path)?;
+        let name = name.unwrap_or_else(|| self::utils::genome_name(&path));
+        // Release the GIL while reading and sketching
+        py.allow_threads(|| {
+            let records = self::utils::read_fastx(&path)?;
+            let contigs = records.iter().map(|(id, seq)| (id.clone(), seq.as_slice()));
+            let sketch = Sketch::from_named_contigs(&self.params, name.clone(), contigs, seed)?;
+            self._add(name, sketch.clone())?;
+            Ok(sketch)
+        })
+    }
+
+    /// Remove a reference genome from the database.
+    ///
+    /// For a database stored in a folder, the markers (and the index, for
+    /// the ``consolidated`` format) are flushed to disk immediately. In
+    /// the ``consolidated`` format, the sketch is only removed from the
+    /// index, and the space it uses in ``sketches.db`` can be reclaimed
+    /// with `Database.compact`.
+    ///
+    /// Arguments:
+    ///     name (`str`): The name of the reference genome to remove.
+    ///
+    /// Raises:
+    ///     `KeyError`: When no reference genome with the given name
+    ///         exists in the database.
+    ///     `OSError`: When the database files could not be updated.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    pub fn remove(&mut self, name: &str) -> PyResult<()> {
+        self._check_writable()?;
+        {
+            let mut markers = self
+                .markers
+                .write()
+                .map_err(|_| self::utils::poisoned_lock_error())?;
+            let mut storage = self
+                .sketches
+                .write()
+                .map_err(|_| self::utils::poisoned_lock_error())?;
+            if !markers
+                .iter()
+                .any(|marker| marker.as_ref().file_name == name)
+            {
+                return Err(PyKeyError::new_err(name.to_string()));
+            }
+            storage.remove(name)?;
+            markers.retain(|marker| marker.as_ref().file_name != name);
+        }
+        self.flush()
+    }
+
+    pub fn __delitem__(&mut self, name: &str) -> PyResult<()> {
+        self.remove(name)
+    }
+
+    pub fn __len__(&self) -> PyResult<usize> {
+        Ok(self
+            .markers
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?
+            .len())
+    }
+
+    pub fn __contains__(&self, name: &Bound<PyAny>) -> PyResult<bool> {
+        match name.extract::<PyBackedStr>() {
+            Ok(name) => Ok(self
+                .sketches
+                .read()
+                .map_err(|_| self::utils::poisoned_lock_error())?
+                .contains(&name)),
+            Err(_) => Ok(false),
+        }
+    }
+
+    pub fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        PyList::new(py, self._names()?)?.try_iter()
+    }
+
+    pub fn __getitem__(&self, name: &str) -> PyResult<Sketch> {
+        match self._get(name)? {
+            Some(sketch) => Ok(sketch),
+            None => Err(PyKeyError::new_err(name.to_string())),
+        }
+    }
+
+    /// Get the names of the reference genomes in the database.
+    ///
+    /// Returns:
+    ///     `list` of `str`: The names of the reference genomes, in the
+    ///     order they were added to the database.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    pub fn names(&self) -> PyResult<Vec<String>> {
+        self._names()
+    }
+
+    /// Get the sketch of a reference genome, if it exists.
+    ///
+    /// Arguments:
+    ///     name (`str`): The name of the reference genome.
+    ///     default (`object`): The value to return when no reference
+    ///         genome with the given name exists in the database.
+    ///
+    /// Returns:
+    ///     `~pyskani.Sketch` or `object`: The sketch of the reference
+    ///     genome, or ``default`` if it could not be found.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[pyo3(signature = (name, default=None))]
+    pub fn get<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        default: Option<Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        match self._get(name)? {
+            Some(sketch) => Ok(Bound::new(py, sketch)?.into_any()),
+            None => Ok(default.unwrap_or_else(|| py.None().into_bound(py))),
+        }
+    }
+
+    /// Copy the references of another database into this database.
+    ///
+    /// Markers and sketches are copied regardless of the storage backend
+    /// of either database, without sketching the genomes again. New
+    /// references are added after the existing ones, in the order of
+    /// the other database.
+    ///
+    /// Arguments:
+    ///     other (`~pyskani.Database`): The database to copy the
+    ///         references from.
+    ///
+    /// Keyword Arguments:
+    ///     duplicates (`str`): The policy for references found in both
+    ///         databases: ``error`` (the default) to fail before copying
+    ///         anything, ``skip`` to keep the existing reference, or
+    ///         ``replace`` to replace it with the one from ``other``.
+    ///
+    /// Returns:
+    ///     `int`: The number of references copied from ``other``.
+    ///
+    /// Raises:
+    ///     `ValueError`: When the databases were created with different
+    ///         sketching parameters, when the policy is invalid, or when
+    ///         a reference is found in both databases with the ``error``
+    ///         policy.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[pyo3(signature = (other, *, duplicates="error"))]
+    pub fn merge<'py>(
+        slf: &Bound<'py, Self>,
+        other: &Bound<'py, Self>,
+        duplicates: &str,
+    ) -> PyResult<usize> {
+        let policy = DuplicatePolicy::parse(duplicates)?;
+        if slf.is(other) {
+            return Err(PyValueError::new_err("cannot merge a database with itself"));
+        }
+        let db = slf.try_borrow()?;
+        let src = other.try_borrow()?;
+        db._check_writable()?;
+        // Release the GIL while copying the sketches
+        let (db, src) = (&*db, &*src);
+        slf.py().allow_threads(|| db._merge(src, policy))
+    }
+
+    /// Add a precomputed sketch to the database.
+    ///
+    /// Arguments:
+    ///     sketch (`~pyskani.Sketch`): The sketch of the reference genome
+    ///         to add, obtained with the same parameters as the database.
+    ///
+    /// Raises:
+    ///     `ValueError`: When the sketch was computed with parameters
+    ///         different from the database parameters, or when another
+    ///         reference with the same file name is stored in a
+    ///         ``separated`` database.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    pub fn add(&mut self, sketch: &Sketch) -> PyResult<()> {
+        self._check_writable()?;
+        self._check_sketch(sketch)?;
+        self._add(sketch.as_ref().file_name.clone(), sketch.clone())
+    }
+
+    /// Query the database with a genome.
+    ///      
+    /// Arguments:
+    ///     name (`str`): The name of the query genome.
+    ///     contigs (`str`, `bytes`, `bytearray` or `memoryview`): The contigs
+    ///         of the query genome.
+    ///
+    /// Keyword Arguments:
+    ///     seed (`bool`): Compute seed positions while sketching
+    ///         the query.
+    ///     learned_ani (`bool` or `None`): Use a regression model to
+    ///         compute ANI, using a model trained on MAGs. Pass `True`
+    ///         or `False` to force enabling or disabling the model,
+    ///         respectively. By default, the regression model is enabled
+    ///         when the sketch compression factor is >=70 and not running
+    ///         in median mode, except for amino-acid databases.
+    ///     median (`bool`): Estimate median identity instead of average
+    ///         identity. Disabled by default. Equivalent to the ``--median``
+    ///         flag of the CLI.
+    ///     robust (`bool`): Estimate mean after trim off 10%/90% quantiles.
+    ///         Disabled by default. Equivalent to the ``--robust`` flag
+    ///         of the CLI.
+    ///     cutoff (`float` or `None`): The cutoff to use to screen out pairs
+    ///         with *approximately* lower identity, as computed with k-mer
+    ///         sketching. Defaults to 0.8 for ANI and 0.6 for AAI.
+    ///         Equivalent to the ``-s`` flag from the CLI.
+    ///     faster_small (`bool`): Set to ``True`` to filter genomes with
+    ///         less than 20 marker k-mers more aggressively. Disabled by
+    ///         default. Equivalent to the ``--faster-small`` flag of the
+    ///         CLI.
+    ///     est_ci (`bool`): Set to ``True`` to estimate confidence
+    ///         intervals for the identity with bootstrapping, reported
+    ///         in `Hit.ci_lower` and `Hit.ci_upper`. Equivalent to the
+    ///         ``--ci`` flag of the CLI.
+    ///     individual_contigs (`bool`): Set to ``True`` to sketch and
+    ///         query each contig of the query genome separately, and
+    ///         report the hits of every contig, named after the genome
+    ///         and the contig index (or after the record identifier in
+    ///         `Database.query_file`). Equivalent to the ``--qi``
+    ///         flag of the CLI.
+    ///     threads (`int`): The number of threads to use for screening
+    ///         and chaining the references. Pass ``0`` (the default) to
+    ///         use the default thread pool, with one thread per CPU.
+    ///
+    /// Returns:
+    ///     `list` of `~pyskani.Hit`: The hits found for the query.
+    ///
+    /// ..versionadded:: 0.2.0
+    ///     The ``cutoff`` and ``faster_small`` keyword arguments.
+    ///
+    /// ..versionadded:: 0.3.0
+    ///     The ``threads``, ``est_ci`` and ``individual_contigs`` keyword
+    ///     arguments.
+    ///   
+    #[pyo3(signature = (name, *contigs, seed=true, learned_ani=None, median=false, robust=false, cutoff=None, faster_small=false, est_ci=false, individual_contigs=false, threads=0))]
+    pub fn query<'py>(
+        &self,
+        name: String,
+        contigs: Bound<'py, PyTuple>,
+        seed: bool,
+        learned_ani: Option<bool>,
+        median: bool,
+        robust: bool,
+        cutoff: Option<f64>,
+        faster_small: bool,
+        est_ci: bool,
+        individual_contigs: bool,
+        threads: usize,
+    ) -> PyResult<Vec<Hit>> {
+        // Get a view on the contigs
+        let contents = contigs
+            .iter()
+            .map(|item| self::utils::Text::new(item.as_borrowed()))
+            .collect::<PyResult<Vec<_>>>()?;
+        let views = contents
+            .iter()
+            .enumerate()
+            .map(|(i, text)| (format!("{}_{}", &name, i), text.as_bytes()))
+            .collect::<Vec<_>>();
+        // Build command parameters
+        let command_params = SearchOptions {
+            learned_ani,
+            median,
+            robust,
+            cutoff,
+            faster_small,
+            est_ci,
+            individual_contigs,
+        }
+        .command_params(&self.params);
+        // Release the GIL while querying
+        let py = contigs.py();
+        py.allow_threads(move || {
+            self::utils::install(threads, || {
+                self._query(name, views, seed, &command_params, individual_contigs)
+            })?
+        })
+    }
+
+    /// Query the database with a genome from a sequence file.
+    ///
+    /// The file may be in FASTA or FASTQ format, and may be compressed
+    /// with ``gzip``, ``bzip2`` or ``xz``. It is read and parsed directly
+    /// in Rust, without building intermediate Python objects. Every
+    /// record of the file is sketched as a contig of the genome.
+    ///
+    /// Arguments:
+    ///     path (`str`, `bytes`, or `os.PathLike`): The path to the
+    ///         sequence file of the query genome.
+    ///     name (`str` or `None`): The name of the query genome. By
+    ///         default, the file name is used without its extension
+    ///         (e.g. ``genome`` for ``genome.fna.gz``).
+    ///
+    /// Keyword Arguments:
+    ///     seed (`bool`): Compute seed positions while sketching
+    ///         the query.
+    ///     learned_ani (`bool` or `None`): Use a regression model to
+    ///         compute ANI. See `Database.query` for more information.
+    ///     median (`bool`): Estimate median identity instead of average
+    ///         identity.
+    ///     robust (`bool`): Estimate mean after trim off 10%/90% quantiles.
+    ///     cutoff (`float` or `None`): The cutoff to use to screen out pairs
+    ///         with *approximately* lower identity.
+    ///     faster_small (`bool`): Set to ``True`` to filter genomes with
+    ///         less than 20 marker k-mers more aggressively.
+    ///     est_ci (`bool`): Set to ``True`` to estimate confidence
+    ///         intervals for the identity with bootstrapping.
+    ///     individual_contigs (`bool`): Set to ``True`` to sketch and
+    ///         query each record of the file separately.
+    ///     threads (`int`): The number of threads to use for screening
+    ///         and chaining the references. Pass ``0`` (the default) to
+    ///         use the default thread pool, with one thread per CPU.
+    ///
+    /// Returns:
+    ///     `list` of `~pyskani.Hit`: The hits found for the query.
+    ///
+    /// Raises:
+    ///     `OSError`: When the file could not be opened or read.
+    ///     `ValueError`: When the file could not be parsed.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[pyo3(signature = (path, name=None, *, seed=true, learned_ani=None, median=false, robust=false, cutoff=None, faster_small=false, est_ci=false, individual_contigs=false, threads=0))]
+    pub fn query_file<'py>(
+        &self,
+        path: &Bound<'py, PyAny>,
+        name: Option<String>,
+        seed: bool,
+        learned_ani: Option<bool>,
+        median: bool,
+        robust: bool,
+        cutoff: Option<f64>,
+        faster_small: bool,
+        est_ci: bool,
+        individual_contigs: bool,
+        threads: usize,
+    ) -> PyResult<Vec<Hit>> {
+        let py = path.py();
+        let path = self::utils::fspath(path)?;
+        let name = name.unwrap_or_else(|| self::utils::genome_name(&path));
+        // Build command parameters
+        let command_params = SearchOptions {
+            learned_ani,
+            median,
+            robust,
+            cutoff,
+            faster_small,
+            est_ci,
+            individual_contigs,
+        }
+        .command_params(&self.params);
+        // Release the GIL while reading and querying
+        py.allow_threads(move || {
+            let records = self::utils::read_fastx(&path)?;
+            let contigs = records.iter().map(|(id, seq)| (id.clone(), seq.as_slice()));
+            self::utils::install(threads, || {
+                self._query(name, contigs, seed, &command_params, individual_contigs)
+            })?
+        })
+    }
+
+    /// Query the database with a precomputed sketch.
+    ///
+    /// Arguments:
+    ///     sketch (`~pyskani.Sketch`): The sketch of the query genome,
+    ///         obtained with the same parameters as the database.
+    ///
+    /// Keyword Arguments:
+    ///     learned_ani (`bool` or `None`): Use a regression model to
+    ///         compute ANI. See `Database.query` for more information.
+    ///     median (`bool`): Estimate median identity instead of average
+    ///         identity.
+    ///     robust (`bool`): Estimate mean after trim off 10%/90% quantiles.
+    ///     cutoff (`float` or `None`): The cutoff to use to screen out pairs
+    ///         with *approximately* lower identity.
+    ///     faster_small (`bool`): Set to ``True`` to filter genomes with
+    ///         less than 20 marker k-mers more aggressively.
+    ///     est_ci (`bool`): Set to ``True`` to estimate confidence
+    ///         intervals for the identity with bootstrapping, reported
+    ///         in `Hit.ci_lower` and `Hit.ci_upper`. Equivalent to the
+    ///         ``--ci`` flag of the CLI.
+    ///     threads (`int`): The number of threads to use for screening
+    ///         and chaining the references. Pass ``0`` (the default) to
+    ///         use the default thread pool, with one thread per CPU.
+    ///
+    /// Returns:
+    ///     `list` of `~pyskani.Hit`: The hits found for the query.
+    ///
+    /// Raises:
+    ///     `ValueError`: When the sketch was computed with parameters
+    ///         different from the database parameters.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[pyo3(signature = (sketch, *, learned_ani=None, median=false, robust=false, cutoff=None, faster_small=false, est_ci=false, threads=0))]
+    pub fn query_sketch<'py>(
+        &self,
+        py: Python<'py>,
+        sketch: &Sketch,
+        learned_ani: Option<bool>,
+        median: bool,
+        robust: bool,
+        cutoff: Option<f64>,
+        faster_small: bool,
+        est_ci: bool,
+        threads: usize,
+    ) -> PyResult<Vec<Hit>> {
+        self._check_sketch(sketch)?;
+        // Build command parameters
+        let command_params = SearchOptions {
+            learned_ani,
+            median,
+            robust,
+            cutoff,
+            faster_small,
+            est_ci,
+            individual_contigs: false,
+        }
+        .command_params(&self.params);
+        // Release the GIL while querying
+        py.allow_threads(|| {
+            self::utils::install(threads, || -> PyResult<Vec<Hit>> {
+                let mut hits = self._search(std::slice::from_ref(sketch), &command_params)?;
+                Ok(hits.pop().unwrap_or_default())
+            })?
+        })
     }
 
-    /// Add a reference genome to the database.
+    /// Query the database with several genomes at once.
+    ///
+    /// Compared to calling `Database.query` in a loop, this method sketches
+    /// and screens all query genomes in parallel, and loads every
+    /// shortlisted reference sketch only once.
     ///
     /// Arguments:
-    ///     name (`str`): The name of the reference genome to add.
-    ///     contigs (`str`, `bytes`, `bytearray` or `memoryview`): The contigs
-    ///         of the reference genome.
+    ///     genomes (iterable of `tuple`): An iterable yielding the query
+    ///         genomes as ``(name, contigs)`` pairs, where ``contigs`` is
+    ///         an iterable of `str`, `bytes`, `bytearray` or `memoryview`
+    ///         objects.
     ///
     /// Keyword Arguments:
     ///     seed (`bool`): Compute seed positions while sketching
-    ///         the query.
+    ///         the queries.
+    ///     learned_ani (`bool` or `None`): Use a regression model to
+    ///         compute ANI. See `Database.query` for more information.
+    ///     median (`bool`): Estimate median identity instead of average
+    ///         identity.
+    ///     robust (`bool`): Estimate mean after trim off 10%/90% quantiles.
+    ///     cutoff (`float` or `None`): The cutoff to use to screen out pairs
+    ///         with *approximately* lower identity.
+    ///     faster_small (`bool`): Set to ``True`` to filter genomes with
+    ///         less than 20 marker k-mers more aggressively.
+    ///     est_ci (`bool`): Set to ``True`` to estimate confidence
+    ///         intervals for the identity with bootstrapping, reported
+    ///         in `Hit.ci_lower` and `Hit.ci_upper`. Equivalent to the
+    ///         ``--ci`` flag of the CLI.
+    ///     threads (`int`): The number of threads to use for sketching,
+    ///         screening and chaining. Pass ``0`` (the default) to use the
+    ///         default thread pool, with one thread per CPU.
     ///
-    #[pyo3(signature = (name, *contigs, seed=true))]
-    pub fn sketch<'py>(
-        &mut self,
-        name: String,
-        contigs: &Bound<'py, PyTuple>,
+    /// Returns:
+    ///     `list` of `list` of `~pyskani.Hit`: The hits found for each
+    ///     query, in the same order as the query genomes.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[pyo3(signature = (genomes, *, seed=true, learned_ani=None, median=false, robust=false, cutoff=None, faster_small=false, est_ci=false, threads=0))]
+    pub fn query_many<'py>(
+        &self,
+        genomes: &Bound<'py, PyAny>,
         seed: bool,
-    ) -> PyResult<()> {
-        // Get a view on the contigs
-        let contents = contigs
-            .into_iter()
-            .map(|item| self::utils::Text::new(item.as_borrowed()))
-            .collect::<PyResult<Vec<_>>>()?;
-        let views = contents.iter().map(|text| text.as_bytes());
-
-        // Release the GIL while sketching
-        let py = contigs.py();
-        let (sketch, marker) = py.allow_threads(|| {
-            self._sketch(name, views, seed).map(|sketch| {
-                let marker = skani::types::Sketch::get_markers_only(sketch.as_ref()).into();
-                (sketch, marker)
-            })
-        })?;
-
-        // Record sketches
-        self.markers
-            .write()
-            .map_err(|_| self::utils::poisoned_lock_error())?
-            .push(marker);
-        self.sketches
-            .write()
-            .map_err(|_| self::utils::poisoned_lock_error())?
-            .store(sketch, &self.params)?;
-        Ok(())
+        learned_ani: Option<bool>,
+        median: bool,
+        robust: bool,
+        cutoff: Option<f64>,
+        faster_small: bool,
+        est_ci: bool,
+        threads: usize,
+    ) -> PyResult<Vec<Vec<Hit>>> {
+        // Get a view on the contigs of every genome
+        let mut contents = Vec::new();
+        for item in genomes.try_iter()? {
+            let (name, contigs) = item?.extract::<(String, Bound<'py, PyAny>)>()?;
+            contents.push((name, self::utils::Text::extract_contigs(&contigs)?));
+        }
+        // Build command parameters
+        let command_params = SearchOptions {
+            learned_ani,
+            median,
+            robust,
+            cutoff,
+            faster_small,
+            est_ci,
+            individual_contigs: false,
+        }
+        .command_params(&self.params);
+        // Release the GIL while querying
+        let py = genomes.py();
+        py.allow_threads(move || {
+            self::utils::install(threads, || -> PyResult<Vec<Vec<Hit>>> {
+                // Sketch queries
+                let queries = contents
+                    .par_iter()
+                    .map(|(name, texts)| {
+                        let views = texts.iter().map(|text| text.as_bytes());
+                        self._sketch(name.clone(), views, seed)
+                    })
+                    .collect::<PyResult<Vec<_>>>()?;
+                // Search the database
+                self._search(&queries, &command_params)
+            })?
+        })
     }
 
-    /// Query the database with a genome.
-    ///      
-    /// Arguments:
-    ///     name (`str`): The name of the query genome.
-    ///     contigs (`str`, `bytes`, `bytearray` or `memoryview`): The contigs
-    ///         of the query genome.
+    /// Compare every genome of the database against every other genome.
+    ///
+    /// This is equivalent to the ``skani triangle`` command. Each pair of
+    /// genomes is only compared once, using the genome stored first as
+    /// the reference, and pairs are screened with marker k-mers before
+    /// being chained.
     ///
     /// Keyword Arguments:
-    ///     seed (`bool`): Compute seed positions while sketching
-    ///         the query.
+    ///     sparse (`bool`): Set to ``True`` to return a list of hits for
+    ///         the pairs of genomes passing the screen instead of dense
+    ///         matrices. Equivalent to the ``--sparse`` flag of the CLI.
+    ///     diagonal (`bool`): Set to ``True`` to report self-comparisons
+    ///         in sparse mode. Equivalent to the ``--diagonal`` flag of
+    ///         the CLI. Dense matrices always contain the diagonal.
+    ///     distance (`bool`): Set to ``True`` to report distances
+    ///         (``1 - identity``) instead of identities, in the dense
+    ///         identity matrix or in the `Hit.identity` of sparse hits.
+    ///         Equivalent to the ``--distance`` flag of the CLI, which
+    ///         reports ``100 - ANI`` since it uses percentages.
     ///     learned_ani (`bool` or `None`): Use a regression model to
-    ///         compute ANI, using a model trained on MAGs. Pass `True`
-    ///         or `False` to force enabling or disabling the model,
-    ///         respectively. By default, the regression model is enabled
-    ///         when the sketch compression factor is >=70 and not running
-    ///         in median mode.
+    ///         compute ANI. See `Database.query` for more information.
     ///     median (`bool`): Estimate median identity instead of average
-    ///         identity. Disabled by default. Equivalent to the ``--median``
-    ///         flag of the CLI.
+    ///         identity.
     ///     robust (`bool`): Estimate mean after trim off 10%/90% quantiles.
-    ///         Disabled by default. Equivalent to the ``--robust`` flag
-    ///         of the CLI.
     ///     cutoff (`float` or `None`): The cutoff to use to screen out pairs
-    ///         with *approximately* lower identity, as computed with k-mer
-    ///         sketching. Defaults to 0.8 for ANI and 0.6 for AAI.
-    ///         Equivalent to the ``-s`` flag from the CLI.
+    ///         with *approximately* lower identity.
     ///     faster_small (`bool`): Set to ``True`` to filter genomes with
-    ///         less than 20 marker k-mers more aggressively. Disabled by
-    ///         default. Equivalent to the ``--faster-small`` flag of the
-    ///         CLI.
+    ///         less than 20 marker k-mers more aggressively.
+    ///     est_ci (`bool`): Set to ``True`` to estimate confidence
+    ///         intervals for the identity with bootstrapping, reported
+    ///         in `Hit.ci_lower` and `Hit.ci_upper`. Equivalent to the
+    ///         ``--ci`` flag of the CLI.
+    ///     threads (`int`): The number of threads to use for screening
+    ///         and chaining. Pass ``0`` (the default) to use the default
+    ///         thread pool, with one thread per CPU.
     ///
     /// Returns:
-    ///     `list` of `~pyskani.Hit`: The hits found for the query.
+    ///     `tuple` or `list` of `~pyskani.Hit`: In dense mode, a tuple
+    ///     containing the list of genome names, and three square matrices
+    ///     (as `list` of `list` of `float`) with the identity, the
+    ///     reference fraction and the query fraction, where the cell at
+    ///     row *i* and column *j* compares reference *i* with query *j*.
+    ///     Pairs rejected by the screen have an identity of zero. In
+    ///     sparse mode, the list of hits between pairs of genomes.
     ///
-    /// ..versionadded:: 0.2.0
-    ///     The ``cutoff`` and ``faster_small`` keyword arguments.
-    ///   
-    #[pyo3(signature = (name, *contigs, seed=true, learned_ani=None, median=false, robust=false, cutoff=None, faster_small=false))]
-    pub fn query<'py>(
+    /// .. versionadded:: 0.3.0
+    ///
+    #[pyo3(signature = (*, sparse=false, diagonal=false, distance=false, learned_ani=None, median=false, robust=false, cutoff=None, faster_small=false, est_ci=false, threads=0))]
+    pub fn triangle<'py>(
         &self,
-        name: String,
-        contigs: Bound<'py, PyTuple>,
-        seed: bool,
+        py: Python<'py>,
+        sparse: bool,
+        diagonal: bool,
+        distance: bool,
         learned_ani: Option<bool>,
         median: bool,
         robust: bool,
         cutoff: Option<f64>,
         faster_small: bool,
-    ) -> PyResult<Vec<Hit>> {
-        // Get a view on the contigs
-        let contents = contigs
-            .iter()
-            .map(|item| self::utils::Text::new(item.as_borrowed()))
-            .collect::<PyResult<Vec<_>>>()?;
-        let views = contents.iter().map(|text| text.as_bytes());
-        // Release the GIL while querying
-        let py = contigs.py();
-        py.allow_threads(move || {
-            // Sketch query
-            let query = self._sketch(name, views, seed)?;
-            // Build command parameters
-            let command_params = CommandParams {
-                screen: false,
-                screen_val: cutoff.unwrap_or(0.0),
-                mode: skani::params::Mode::Search,
-                out_file_name: Default::default(),
-                ref_files: Default::default(),
-                query_files: Default::default(),
-                refs_are_sketch: true,
-                queries_are_sketch: true,
-                robust,
-                median,
-                sparse: false,
-                full_matrix: false,
-                max_results: 1_000_000_000,
-                individual_contig_q: false,
-                individual_contig_r: false,
-                min_aligned_frac: skani::params::D_FRAC_COVER_CUTOFF.parse::<f64>().unwrap()
-                    / 100.0,
-                keep_refs: true,
-                est_ci: Default::default(),
-                learned_ani: learned_ani.unwrap_or(false),
-                detailed_out: false,
-                diagonal: false,
-                distance: false,
-                rescue_small: !faster_small,
-                separate_sketches: false,
-                both_min_aligned_frac: -0.01,
-                short_header: false,
-            };
-            // Get cutoff for screening
-            let screen_val = if command_params.screen_val != 0.0 {
-                command_params.screen_val
-            } else if self.params.use_aa {
-                skani::params::SEARCH_AAI_CUTOFF_DEFAULT
-            } else {
-                skani::params::SEARCH_ANI_CUTOFF_DEFAULT
-            };
-            // Load regression model if requested / necessary
-            let learned = learned_ani.unwrap_or_else(|| {
-                skani::regression::use_learned_ani(self.params.c, false, false, median)
-            });
-            let model_opt = skani::regression::get_model(self.params.c, learned);
-            // Search marker sketches first
-            let mut shortlist = HashSet::new();
-            for marker in self
-                .markers
-                .read()
-                .map_err(|_| self::utils::poisoned_lock_error())?
-                .iter()
-            {
-                if skani::screen::check_markers_quickly(
-                    query.as_ref(),
-                    marker.as_ref(),
-                    screen_val,
-                    command_params.rescue_small,
-                ) {
-                    let name = Path::new(&marker.as_ref().file_name)
-                        .file_name()
-                        .unwrap()
-                        .to_os_string()
-                        .into_string()
-                        .unwrap();
-                    shortlist.insert(name);
-                }
-            }
-            // Search full sketches
-            let mut hits = Vec::new();
-            for name in shortlist.iter() {
-                let guard = self
+        est_ci: bool,
+        threads: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        // Build command parameters
+        let mut command_params = SearchOptions {
+            learned_ani,
+            median,
+            robust,
+            cutoff,
+            faster_small,
+            est_ci,
+            individual_contigs: false,
+        }
+        .command_params(&self.params);
+        command_params.mode = skani::params::Mode::Triangle;
+        // Release the GIL while comparing
+        let (names, results) = py.allow_threads(|| {
+            self::utils::install(threads, || -> PyResult<_> {
+                // Get cutoff for screening
+                let screen_val = if command_params.screen_val != 0.0 {
+                    command_params.screen_val
+                } else if self.params.use_aa {
+                    skani::params::SEARCH_AAI_CUTOFF_DEFAULT
+                } else {
+                    skani::params::SEARCH_ANI_CUTOFF_DEFAULT
+                };
+                // Load regression model if requested
+                let model_opt =
+                    skani::regression::get_model(self.params.c, command_params.learned_ani);
+                // Load all sketches once
+                let markers = self
+                    .markers
+                    .read()
+                    .map_err(|_| self::utils::poisoned_lock_error())?;
+                let storage = self
                     .sketches
                     .read()
                     .map_err(|_| self::utils::poisoned_lock_error())?;
-                let reference = &*guard.load(&name)?;
-                let map_params = skani::chain::map_params_from_sketch(
-                    reference.as_ref(),
-                    self.params.use_aa,
-                    &command_params,
-                    &model_opt,
-                );
-                let ani_res =
-                    skani::chain::chain_seeds(reference.as_ref(), query.as_ref(), map_params);
-                if ani_res.ani > 0.1 {
+                let sketches = markers
+                    .par_iter()
+                    .map(|marker| storage.load(&marker.as_ref().file_name))
+                    .collect::<PyResult<Vec<_>>>()?;
+                // Compare every pair of genomes
+                let n = sketches.len();
+                let results = (0..n)
+                    .into_par_iter()
+                    .flat_map_iter(|i| (i + 1..n).map(move |j| (i, j)))
+                    .map(|(i, j)| -> PyResult<Option<_>> {
+                        let reference = (*sketches[i]).as_ref();
+                        let query = (*sketches[j]).as_ref();
+                        let genomes = format_args!(
+                            "{:?} against {:?}",
+                            markers[j].as_ref().file_name,
+                            markers[i].as_ref().file_name
+                        );
+                        let candidate = self::utils::catch_panic("screening", genomes, || {
+                            skani::screen::check_markers_quickly(
+                                markers[j].as_ref(),
+                                markers[i].as_ref(),
+                                screen_val,
+                                command_params.rescue_small,
+                            )
+                        })?;
+                        if !candidate {
+                            return Ok(None);
+                        }
+                        let ani_res = self::utils::catch_panic("comparing", genomes, || {
+                            let map_params = skani::chain::map_params_from_sketch(
+                                reference,
+                                self.params.use_aa,
+                                &command_params,
+                                &model_opt,
+                            );
+                            skani::chain::chain_seeds(reference, query, map_params)
+                        })?;
+                        if ani_res.ani > 0.1 {
+                            Ok(Some((i, j, ani_res)))
+                        } else {
+                            Ok(None)
+                        }
+                    })
+                    .collect::<PyResult<Vec<_>>>()?
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>();
+                let names = markers
+                    .iter()
+                    .map(|marker| marker.as_ref().file_name.clone())
+                    .collect::<Vec<_>>();
+                Ok((names, results))
+            })?
+        })?;
+
+        if sparse {
+            // Report hits in row order, with self-comparisons first
+            let mut hits = Vec::with_capacity(results.len() + names.len());
+            let mut results = results.into_iter().peekable();
+            for (i, name) in names.iter().enumerate() {
+                if diagonal {
+                    hits.push(Hit::from(AniEstResult {
+                        ani: if distance { 0.0 } else { 1.0 },
+                        align_fraction_query: 1.0,
+                        align_fraction_ref: 1.0,
+                        ref_file: name.clone(),
+                        query_file: name.clone(),
+                        ..Default::default()
+                    }));
+                }
+                while let Some((_, _, mut ani_res)) = results.next_if(|(r, _, _)| *r == i) {
+                    if distance {
+                        ani_res.ani = 1.0 - ani_res.ani;
+                    }
                     hits.push(Hit::from(ani_res));
                 }
             }
-            Ok(hits)
-        })
+            Ok(hits.into_pyobject(py)?.into_any())
+        } else {
+            // Fill the matrices symmetrically, swapping the fractions
+            let n = names.len();
+            let mut identity = vec![vec![0.0f32; n]; n];
+            let mut reference_fraction = vec![vec![0.0f32; n]; n];
+            let mut query_fraction = vec![vec![0.0f32; n]; n];
+            for i in 0..n {
+                identity[i][i] = 1.0;
+                reference_fraction[i][i] = 1.0;
+                query_fraction[i][i] = 1.0;
+            }
+            for (i, j, ani_res) in results {
+                identity[i][j] = ani_res.ani;
+                identity[j][i] = ani_res.ani;
+                reference_fraction[i][j] = ani_res.align_fraction_ref;
+                reference_fraction[j][i] = ani_res.align_fraction_query;
+                query_fraction[i][j] = ani_res.align_fraction_query;
+                query_fraction[j][i] = ani_res.align_fraction_ref;
+            }
+            if distance {
+                for x in identity.iter_mut().flatten() {
+                    *x = 1.0 - *x;
+                }
+            }
+            let matrices = (names, identity, reference_fraction, query_fraction);
+            Ok(matrices.into_pyobject(py)?.into_any())
+        }
     }
 
     /// Save the database to the given path.
+    ///
+    /// The sketches are written in the requested format to a staging
+    /// folder, which is then opened again to check that all reference
+    /// names and sketching parameters were written correctly. The files
+    /// of an existing database are only replaced afterwards, all at once,
+    /// and its stale sketch files are removed.
+    ///
+    /// Arguments:
+    ///     path (`str`, `bytes`, or `os.PathLike`): The path to the folder
+    ///         where to save the database. A new folder will be created if
+    ///         it does not exist.
+    ///     overwrite (`bool`): Set to ``True`` to replace an existing
+    ///         database in the folder instead of raising an error.
+    ///     format (`str`): The database format to use. Use either
+    ///         ``separated`` to write one sketch file per sketch genome
+    ///         (as in ``skani<0.3.0``), or ``consolidated`` (the default)
+    ///         to write a single sketch file (as in ``skani>=0.3.0``)
+    ///
+    /// Raises:
+    ///     `OSError`: When the folder or the files could not be created.
+    ///     `FileExistsError`: When the folder already contains a database
+    ///         or sketch files, and ``overwrite`` is `False`.
+    ///     `BlockingIOError`: When the folder is locked by a database
+    ///         opened in ``a`` mode.
+    ///     `ValueError`: When the format is invalid, when attempting to
+    ///         save the database to the folder it is stored in, or when
+    ///         the saved database could not be verified.
+    ///
+    /// .. versionchanged:: 0.3.0
+    ///     Fix the ``format`` argument producing the opposite layout, and
+    ///     verify the saved database.
+    ///
     #[pyo3(signature = (path, overwrite=false, format=None))]
     pub fn save<'py>(
         &self,
@@ -667,11 +2363,24 @@ impl Database {
         overwrite: bool,
         format: Option<String>,
     ) -> PyResult<()> {
-        // obtain Unicode representation of path
-        let path = self::utils::fsdecode(path)?;
+        // obtain the path to the folder, and to the staging folder where
+        // the database is written before replacing an existing database
+        let folder = self::utils::fspath(path)?;
+        let folder = folder.as_path();
+        let staging = folder.join(".staging");
+
+        // decode which format is required
+        let mut storage = match format.as_ref().map(String::as_str) {
+            Some("consolidated") | None => {
+                DatabaseStorage::Consolidated(staging.clone(), HashMap::new(), None, None)
+            }
+            Some("separated") => {
+                DatabaseStorage::Folder(staging.clone(), HashMap::new(), Default::default(), None)
+            }
+            Some(other) => return Err(PyValueError::new_err(format!("invalid format: {}", other))),
+        };
 
         // Create folder if it doesn't exist
-        let folder = Path::new(path.to_str()?);
         if !folder.exists() {
             if let Err(err) = std::fs::create_dir_all(folder) {
                 return if let Some(code) = err.raw_os_error() {
@@ -683,61 +2392,459 @@ impl Database {
             }
         }
 
-        // Serialize the markers
-        let markers_path = folder.join("markers.bin");
-        if !overwrite && markers_path.exists() {
-            return Err(PyFileExistsError::new_err(
-                markers_path.display().to_string(),
-            ));
+        // Refuse to save the database over its own files
+        if let Some(source) = self._folder()? {
+            if source.canonicalize()? == folder.canonicalize()? {
+                return Err(PyValueError::new_err(format!(
+                    "cannot save database to its own folder: {}",
+                    folder.display()
+                )));
+            }
         }
-        self._save_markers(markers_path)?;
 
-        // decode which format is required
-        let mut storage = match format.as_ref().map(String::as_str) {
-            Some("consolidated") | None => DatabaseStorage::Folder(folder.to_owned()),
-            Some("separated") => DatabaseStorage::Consolidated(folder.to_owned(), HashMap::new()),
-            Some(other) => return Err(PyValueError::new_err(format!("invalid format: {}", other))),
-        };
+        // Lock the folder so that no other writer uses it while saving
+        let _lock = utils::lock_folder(folder)?;
 
-        // Serialize the sketches
-        for filename in self
-            .markers
-            .read()
-            .map_err(|_| self::utils::poisoned_lock_error())?
-            .iter()
-            .map(|marker| Path::new(&marker.as_ref().file_name))
-        {
-            let name = filename
-                .file_name()
-                .unwrap()
-                .to_os_string()
-                .into_string()
-                .unwrap(); // FIXME?
-            let sketch = self
-                .sketches
+        // Check for existing database or sketch files, which are only
+        // replaced, if requested, once the new database was written and
+        // verified
+        let mut existing = Vec::new();
+        for entry in std::fs::read_dir(folder)? {
+            if let Ok(file_name) = entry?.file_name().into_string() {
+                if DATABASE_FILES.contains(&file_name.as_str()) || file_name.ends_with(".sketch") {
+                    existing.push(file_name);
+                }
+            }
+        }
+        if !overwrite {
+            if let Some(file_name) = existing.first() {
+                let file_path = folder.join(file_name);
+                return Err(PyFileExistsError::new_err(file_path.display().to_string()));
+            }
+        }
+
+        // Write the database to the staging folder, which is inside the
+        // locked folder and on the same filesystem, and check everything
+        // was saved by opening it again
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+        std::fs::create_dir(&staging)?;
+        let result = (|| {
+            for name in self
+                .markers
                 .read()
                 .map_err(|_| self::utils::poisoned_lock_error())?
-                .load(&name)?
-                .into_owned();
-            storage.store(sketch, &self.params)?;
+                .iter()
+                .map(|marker| marker.as_ref().file_name.clone())
+            {
+                let sketch = self
+                    .sketches
+                    .read()
+                    .map_err(|_| self::utils::poisoned_lock_error())?
+                    .load(&name)?
+                    .into_owned();
+                storage.store(&name, sketch, &self.params)?;
+            }
+            self._flush(&storage)?;
+            self._verify(&Self::open_folder(&staging, 0, false)?)
+        })();
+        if let Err(err) = result {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+
+        // Move the new files next to the files of the existing database,
+        // and replace them all at once, removing stale sketch files
+        let mut replaced = Vec::new();
+        for entry in std::fs::read_dir(&staging)? {
+            let file_name = entry?.file_name();
+            let path = folder.join(&file_name);
+            utils::replace(&staging.join(&file_name), &utils::temporary_path(&path))?;
+            replaced.push(file_name.to_string_lossy().into_owned());
+        }
+        std::fs::remove_dir(&staging)?;
+        let removed = existing
+            .iter()
+            .filter(|file_name| !replaced.contains(file_name))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let replaced = replaced.iter().map(String::as_str).collect::<Vec<_>>();
+        utils::replace_all(folder, &replaced, &removed)
+    }
+
+    /// Compact the sketches of a consolidated database.
+    ///
+    /// Sketches are only ever appended to the ``sketches.db`` file of a
+    /// consolidated database, so removing a reference genome, or an
+    /// interrupted write, leaves unused data behind. This method rewrites
+    /// ``sketches.db`` with only the sketches of the reference genomes in
+    /// the database, in the order they were added, and updates
+    /// ``index.db`` accordingly. This does nothing for databases stored
+    /// in memory or in the ``separated`` format.
+    ///
+    /// Both files are replaced together once the new ones are written, so
+    /// an interrupted compaction is either completed or discarded the
+    /// next time the database is opened.
+    ///
+    /// Returns:
+    ///     `int`: The number of bytes reclaimed from ``sketches.db``.
+    ///
+    /// Raises:
+    ///     `OSError`: When the database files could not be rewritten.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    pub fn compact(&mut self) -> PyResult<u64> {
+        self._check_writable()?;
+        let markers = self
+            .markers
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        let mut storage = self
+            .sketches
+            .write()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        let (folder, index, mmap) = match *storage {
+            DatabaseStorage::Consolidated(ref folder, ref mut index, ref mut mmap, _) => {
+                (folder.clone(), index, mmap)
+            }
+            _ => return Ok(0),
+        };
+
+        // nothing to compact if no sketch was ever written
+        let sketches_path = folder.join("sketches.db");
+        if !sketches_path.exists() {
+            return Ok(0);
         }
+        let old_length = std::fs::metadata(&sketches_path)?.len();
+
+        // copy the sketches in marker order to a temporary file, and write
+        // the new index to a temporary file as well
+        let index_path = folder.join("index.db");
+        let compacted_path = utils::temporary_path(&sketches_path);
+        let compacted_index_path = utils::temporary_path(&index_path);
+        let result = (|| {
+            let mut reader = utils::buffered_open(&sketches_path)?;
+            let mut writer = utils::buffered_create(&compacted_path)?;
+            let mut compacted = HashMap::new();
+            let mut buffer = Vec::new();
+            let mut offset = 0;
+            for marker in markers.iter() {
+                let name = &marker.as_ref().file_name;
+                if compacted.contains_key(name) {
+                    continue;
+                }
+                let entry = match index.get(name) {
+                    Some(entry) => entry,
+                    None => return Err(PyKeyError::new_err(name.clone())),
+                };
+                buffer.resize(entry.length as usize, 0);
+                reader.seek(SeekFrom::Start(entry.offset))?;
+                reader.read_exact(&mut buffer)?;
+                writer.write_all(&buffer)?;
+                let compacted_entry = IndexEntry {
+                    file_name: name.clone(),
+                    offset,
+                    length: entry.length,
+                };
+                compacted.insert(name.clone(), compacted_entry);
+                offset += entry.length;
+            }
+            utils::sync(writer)?;
+            let mut writer = utils::buffered_create(&compacted_index_path)?;
+            self._write_index(&mut writer, &compacted)?;
+            utils::sync(writer)?;
+            Ok((compacted, offset))
+        })();
+        let (compacted, offset) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                let _ = std::fs::remove_file(&compacted_path);
+                let _ = std::fs::remove_file(&compacted_index_path);
+                return Err(err);
+            }
+        };
+
+        // replace the sketches and the index once both are on disk, through
+        // a journal so that opening the database completes an interrupted
+        // replacement, and closing the previous sketches first since they
+        // cannot be replaced while mapped on some platforms
+        *mmap = None;
+        utils::replace_all(&folder, &["sketches.db", "index.db"], &[])?;
+        *index = compacted;
+        *mmap = utils::mmap_open(&sketches_path, offset)?;
+
+        Ok(old_length - offset)
+    }
 
-        self._flush(&storage)
+    /// Get statistics about the sketch cache of the database.
+    ///
+    /// Returns:
+    ///     `dict`: A dictionary with the number of cache ``hits`` and
+    ///     ``misses``, the maximum number of cached sketches ``maxsize``,
+    ///     and the current number of cached sketches ``currsize``, like
+    ///     `functools.lru_cache`. All values are zero when the database
+    ///     was not opened with a ``cache_size``.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    pub fn cache_info<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let storage = self
+            .sketches
+            .read()
+            .map_err(|_| self::utils::poisoned_lock_error())?;
+        let (hits, misses, maxsize, currsize) = match storage.cache() {
+            None => (0, 0, 0, 0),
+            Some(cache) => (
+                cache.hits.load(Ordering::Relaxed),
+                cache.misses.load(Ordering::Relaxed),
+                cache.capacity.get(),
+                cache
+                    .sketches
+                    .lock()
+                    .map_err(|_| self::utils::poisoned_lock_error())?
+                    .len(),
+            ),
+        };
+        let info = PyDict::new(py);
+        info.set_item("hits", hits)?;
+        info.set_item("misses", misses)?;
+        info.set_item("maxsize", maxsize)?;
+        info.set_item("currsize", currsize)?;
+        Ok(info)
     }
 
     /// Flush the database buffers to disk.
     ///
     /// This does nothing for a database loaded in memory. For a database
-    /// stored in a folder, this will save the markers into a file named
-    /// ``markers.bin``.
+    /// stored in a folder, the sketches added since the last flush are
+    /// synchronized to disk, and the markers are then saved into a file
+    /// named ``markers.bin``, and the sketch index of a consolidated
+    /// database into a file named ``index.db``. Both files are replaced
+    /// together, so an interrupted flush is completed the next time the
+    /// database is opened.
     ///
     pub fn flush(&self) -> PyResult<()> {
+        if !self.writable {
+            return Ok(());
+        }
         if let Ok(sketches) = self.sketches.read() {
             self._flush(&*sketches)
         } else {
             Err(self::utils::poisoned_lock_error())
         }
     }
+
+    /// Flush the database and release the lock on its folder.
+    ///
+    /// A database stored in a folder can still be queried once closed,
+    /// but no sketches can be added or removed anymore, as if it had been
+    /// opened in read-only mode. This does nothing for a database loaded
+    /// in memory, or for a database that is already closed.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    pub fn close(&mut self) -> PyResult<()> {
+        self.flush()?;
+        if self.lock.take().is_some() {
+            self.writable = false;
+        }
+        Ok(())
+    }
+}
+
+/// Merge several databases into a new database.
+///
+/// The new database is created with the sketching parameters of the
+/// first database, and the references of each database are copied in
+/// order with `Database.merge`.
+///
+/// Arguments:
+///     paths (iterable of `str`, `bytes`, or `os.PathLike`): The paths
+///         to the folders of the databases to merge.
+///     out (`str`, `bytes`, or `os.PathLike`): The path of the folder to
+///         store the merged database into.
+///
+/// Keyword Arguments:
+///     duplicates (`str`): The policy for references found in several
+///         databases, either ``error`` (the default), ``skip`` or
+///         ``replace``. See `Database.merge` for more information.
+///     format (`str`): The database format to use, either
+///         ``separated`` or ``consolidated`` (the default).
+///
+/// Returns:
+///     `~pyskani.Database`: The merged database.
+///
+/// Raises:
+///     `OSError`: When a database could not be opened.
+///     `ValueError`: When no database was given, when the databases
+///         were created with different sketching parameters, or when a
+///         reference is found in several databases with the ``error``
+///         policy.
+///     `FileExistsError`: When the output folder already contains
+///         sketches.
+///
+/// .. versionadded:: 0.3.0
+///
+#[pyfunction]
+#[pyo3(signature = (paths, out, *, duplicates="error", format=None))]
+pub fn merge<'py>(
+    py: Python<'py>,
+    paths: &Bound<'py, PyAny>,
+    out: &Bound<'py, PyAny>,
+    duplicates: &str,
+    format: Option<String>,
+) -> PyResult<Database> {
+    let policy = DuplicatePolicy::parse(duplicates)?;
+    let folders = paths
+        .try_iter()?
+        .map(|item| self::utils::fspath(&item?))
+        .collect::<PyResult<Vec<_>>>()?;
+    let inputs = folders
+        .iter()
+        .map(|folder| Database::open_folder(folder, 0, false))
+        .collect::<PyResult<Vec<_>>>()?;
+    let first = match inputs.first() {
+        Some(first) => first,
+        None => return Err(PyValueError::new_err("no database to merge")),
+    };
+
+    // check all inputs before creating the output database, so that an
+    // incompatible input does not leave a partial database behind
+    let mut names = HashSet::new();
+    for input in &inputs {
+        first._check_params(&input.params)?;
+        if policy == DuplicatePolicy::Error {
+            for name in input._names()? {
+                if !names.insert(name.clone()) {
+                    return Err(PyValueError::new_err(format!(
+                        "duplicate reference name: {:?}",
+                        name
+                    )));
+                }
+            }
+        }
+    }
+
+    // create the output database with the parameters of the first input
+    let db = Database::new(Some(out), first.params.clone(), format)?;
+
+    // Release the GIL while copying the sketches
+    py.allow_threads(|| {
+        for input in &inputs {
+            db._merge(input, policy)?;
+        }
+        Ok::<_, PyErr>(())
+    })?;
+
+    Ok(db)
+}
+
+/// Compute the average nucleotide identity between two genomes.
+///
+/// Both genomes are sketched with the same parameters and compared
+/// directly, without screening the pair with marker k-mers first.
+/// This is equivalent to the ``skani dist`` command.
+///
+/// Arguments:
+///     reference (`str`, `bytes`, `bytearray`, `memoryview`, or iterable):
+///         The reference genome, given either as a single sequence, or
+///         as an iterable of contigs.
+///     query (`str`, `bytes`, `bytearray`, `memoryview`, or iterable):
+///         The query genome, given either as a single sequence, or as an
+///         iterable of contigs.
+///
+/// Keyword Arguments:
+///     reference_name (`str`): The name of the reference genome.
+///     query_name (`str`): The name of the query genome.
+///     parameters (`~pyskani.SketchParameters`): The parameters to use
+///         for sketching. Cannot be combined with the ``compression``,
+///         ``marker_compression`` and ``k`` arguments.
+///     compression (`int`): The compression factor for sketches.
+///     marker_compression (`int`): The compression factor for marker
+///         k-mers.
+///     k (`int`): The k-mer size to use for sketching.
+///     seed (`bool`): Compute seed positions while sketching the genomes.
+///     learned_ani (`bool` or `None`): Use a regression model to
+///         compute ANI. See `Database.query` for more information.
+///     median (`bool`): Estimate median identity instead of average
+///         identity.
+///     robust (`bool`): Estimate mean after trim off 10%/90% quantiles.
+///     est_ci (`bool`): Set to ``True`` to estimate confidence
+///         intervals for the identity with bootstrapping, reported
+///         in `Hit.ci_lower` and `Hit.ci_upper`. Equivalent to the
+///         ``--ci`` flag of the CLI.
+///
+/// Returns:
+///     `~pyskani.Hit`: The hit between the reference and the query genome.
+///
+/// .. versionadded:: 0.3.0
+///
+#[pyfunction]
+#[pyo3(signature = (reference, query, *, reference_name="reference", query_name="query", parameters=None, compression=None, marker_compression=None, k=None, seed=true, learned_ani=None, median=false, robust=false, est_ci=false))]
+pub fn dist<'py>(
+    reference: &Bound<'py, PyAny>,
+    query: &Bound<'py, PyAny>,
+    reference_name: &str,
+    query_name: &str,
+    parameters: Option<PyRef<'py, SketchParameters>>,
+    compression: Option<usize>,
+    marker_compression: Option<usize>,
+    k: Option<usize>,
+    seed: bool,
+    learned_ani: Option<bool>,
+    median: bool,
+    robust: bool,
+    est_ci: bool,
+) -> PyResult<Hit> {
+    let params =
+        SketchParameters::resolve(parameters.as_deref(), compression, marker_compression, k)?;
+    // Get a view on the contigs
+    let reference_contigs = self::utils::Text::extract_contigs(reference)?;
+    let query_contigs = self::utils::Text::extract_contigs(query)?;
+    // Build command parameters
+    let mut command_params = SearchOptions {
+        learned_ani,
+        median,
+        robust,
+        cutoff: None,
+        faster_small: false,
+        est_ci,
+        individual_contigs: false,
+    }
+    .command_params(&params);
+    command_params.mode = skani::params::Mode::Dist;
+    // Release the GIL while comparing
+    let py = reference.py();
+    py.allow_threads(|| {
+        // Sketch both genomes
+        let reference = Sketch::new(
+            &params,
+            reference_name.to_string(),
+            reference_contigs.iter().map(|text| text.as_bytes()),
+            seed,
+        )?;
+        let query = Sketch::new(
+            &params,
+            query_name.to_string(),
+            query_contigs.iter().map(|text| text.as_bytes()),
+            seed,
+        )?;
+        // Chain seeds without screening
+        let model_opt = skani::regression::get_model(params.c, command_params.learned_ani);
+        let genomes = format_args!("{:?} against {:?}", query_name, reference_name);
+        let ani_res = self::utils::catch_panic("comparing", genomes, || {
+            let map_params = skani::chain::map_params_from_sketch(
+                reference.as_ref(),
+                params.use_aa,
+                &command_params,
+                &model_opt,
+            );
+            skani::chain::chain_seeds(reference.as_ref(), query.as_ref(), map_params)
+        })?;
+        Ok(Hit::from(ani_res))
+    })
 }
 
 /// A Python module for metagenomic sequence comparison with ``skani``.
@@ -753,6 +2860,11 @@ pub fn init<'py>(py: Python<'py>, m: &Bound<'py, PyModule>) -> PyResult<()> {
     m.add_class::<Database>()?;
     m.add_class::<Hit>()?;
     m.add_class::<Sketch>()?;
+    m.add_class::<SketchParameters>()?;
+    m.add("SkaniError", py.get_type::<SkaniError>())?;
+
+    m.add_function(wrap_pyfunction!(dist, m)?)?;
+    m.add_function(wrap_pyfunction!(merge, m)?)?;
 
     Ok(())
 }
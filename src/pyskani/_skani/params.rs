@@ -0,0 +1,252 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use pyo3::types::PyTuple;
+use pyo3::types::PyType;
+use skani::params::SketchParams;
+
+/// The largest nucleotide k-mer size that fits in the k-mer encoding.
+const MAX_K: usize = 32;
+/// The largest amino-acid k-mer size that fits in the k-mer encoding.
+const MAX_K_AAI: usize = 12;
+
+/// The default parameters for nucleotide sketches, as in ``skani sketch``.
+const DEFAULT_C: usize = 125;
+const DEFAULT_K: usize = 15;
+/// The default parameters for amino-acid sketches, as in ``skani sketch -a``.
+const DEFAULT_C_AAI: usize = 15;
+const DEFAULT_K_AAI: usize = 6;
+/// The default compression factor for marker k-mers.
+const DEFAULT_MARKER_C: usize = 1000;
+
+/// The parameters used to sketch genomes.
+///
+/// Sketches can only be compared when they were computed with the same
+/// parameters, so the same `SketchParameters` can be passed to a
+/// `~pyskani.Database`, to `Sketch.from_contigs` or to `~pyskani.dist`
+/// to make sure they agree.
+///
+/// Attributes:
+///     compression (`int`): The compression factor for sketches.
+///     marker_compression (`int`): The compression factor for marker
+///         k-mers.
+///     k (`int`): The k-mer size used for sketching.
+///     amino_acid (`bool`): Whether genomes are sketched with amino-acid
+///         k-mers.
+///     use_syncs (`bool`): Whether genomes are sketched with syncmers.
+///     orf_size (`int`): The minimum length of open reading frames used
+///         for amino-acid sketching.
+///
+/// .. versionadded:: 0.3.0
+///
+#[pyclass(module = "pyskani._skani", frozen)]
+#[derive(Clone)]
+pub struct SketchParameters {
+    params: SketchParams,
+}
+
+impl SketchParameters {
+    /// Create new sketching parameters, validating the given values.
+    pub fn new(
+        compression: usize,
+        marker_compression: usize,
+        k: usize,
+        amino_acid: bool,
+    ) -> PyResult<Self> {
+        if compression == 0 {
+            return Err(PyValueError::new_err(
+                "compression must be strictly positive",
+            ));
+        }
+        if marker_compression == 0 {
+            return Err(PyValueError::new_err(
+                "marker_compression must be strictly positive",
+            ));
+        }
+        let max_k = if amino_acid { MAX_K_AAI } else { MAX_K };
+        if k == 0 || k > max_k {
+            return Err(PyValueError::new_err(format!(
+                "k must be between 1 and {}, got {}",
+                max_k, k
+            )));
+        }
+        Ok(Self {
+            params: SketchParams::new(marker_compression, compression, k, false, amino_acid),
+        })
+    }
+
+    /// Create new sketching parameters, using the defaults of ``skani``
+    /// for the sketch type for missing values.
+    pub fn with_defaults(
+        compression: Option<usize>,
+        marker_compression: Option<usize>,
+        k: Option<usize>,
+        amino_acid: bool,
+    ) -> PyResult<Self> {
+        let (default_c, default_k) = if amino_acid {
+            (DEFAULT_C_AAI, DEFAULT_K_AAI)
+        } else {
+            (DEFAULT_C, DEFAULT_K)
+        };
+        Self::new(
+            compression.unwrap_or(default_c),
+            marker_compression.unwrap_or(DEFAULT_MARKER_C),
+            k.unwrap_or(default_k),
+            amino_acid,
+        )
+    }
+
+    /// Get the parameters given either as an object or as loose keywords.
+    pub fn resolve(
+        parameters: Option<&Self>,
+        compression: Option<usize>,
+        marker_compression: Option<usize>,
+        k: Option<usize>,
+    ) -> PyResult<SketchParams> {
+        match parameters {
+            Some(parameters) => {
+                if compression.is_some() || marker_compression.is_some() || k.is_some() {
+                    return Err(PyTypeError::new_err(
+                        "cannot give both `parameters` and `compression`, `marker_compression` or `k`",
+                    ));
+                }
+                Ok(parameters.params.clone())
+            }
+            None => Self::with_defaults(compression, marker_compression, k, false)
+                .map(|parameters| parameters.params),
+        }
+    }
+}
+
+impl AsRef<SketchParams> for SketchParameters {
+    fn as_ref(&self) -> &SketchParams {
+        &self.params
+    }
+}
+
+impl From<SketchParams> for SketchParameters {
+    fn from(params: SketchParams) -> Self {
+        Self { params }
+    }
+}
+
+#[pymethods]
+impl SketchParameters {
+    /// Create new sketching parameters.
+    ///
+    /// Arguments:
+    ///     compression (`int`): The compression factor for sketches.
+    ///         Defaults to ``125`` for nucleotide sketches, and to ``15``
+    ///         for amino-acid sketches.
+    ///     marker_compression (`int`): The compression factor for marker
+    ///         k-mers. Defaults to ``1000``.
+    ///     k (`int`): The k-mer size to use for sketching. Defaults to
+    ///         ``15`` for nucleotide sketches, and to ``6`` for amino-acid
+    ///         sketches.
+    ///     amino_acid (`bool`): Sketch genomes with amino-acid k-mers
+    ///         obtained from the translated open reading frames, to
+    ///         compute average amino-acid identity (AAI).
+    ///
+    /// Raises:
+    ///     `ValueError`: When any of the parameters is out of range.
+    ///
+    #[new]
+    #[pyo3(signature = (compression=None, marker_compression=None, k=None, amino_acid=false))]
+    pub fn __init__(
+        compression: Option<usize>,
+        marker_compression: Option<usize>,
+        k: Option<usize>,
+        amino_acid: bool,
+    ) -> PyResult<Self> {
+        Self::with_defaults(compression, marker_compression, k, amino_acid)
+    }
+
+    pub fn __repr__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let template = PyString::new(
+            py,
+            "SketchParameters(compression={!r}, marker_compression={!r}, k={!r}, amino_acid={!r})",
+        );
+        template.call_method1(pyo3::intern!(py, "format"), self.args(py)?)
+    }
+
+    pub fn __eq__(&self, other: PyRef<'_, Self>) -> bool {
+        self.key() == other.key()
+    }
+
+    pub fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.key().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Reduce the parameters for pickling.
+    pub fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>)> {
+        Ok((slf.get_type(), slf.get().args(slf.py())?))
+    }
+
+    /// `int`: The compression factor for sketches.
+    #[getter]
+    pub fn get_compression(&self) -> usize {
+        self.params.c
+    }
+
+    /// `int`: The compression factor for marker k-mers.
+    #[getter]
+    pub fn get_marker_compression(&self) -> usize {
+        self.params.marker_c
+    }
+
+    /// `int`: The k-mer size used for sketching.
+    #[getter]
+    pub fn get_k(&self) -> usize {
+        self.params.k
+    }
+
+    /// `bool`: Whether genomes are sketched with amino-acid k-mers.
+    #[getter]
+    pub fn get_amino_acid(&self) -> bool {
+        self.params.use_aa
+    }
+
+    /// `bool`: Whether genomes are sketched with syncmers.
+    #[getter]
+    pub fn get_use_syncs(&self) -> bool {
+        self.params.use_syncs
+    }
+
+    /// `int`: The minimum length of open reading frames.
+    #[getter]
+    pub fn get_orf_size(&self) -> usize {
+        self.params.orf_size
+    }
+}
+
+impl SketchParameters {
+    fn key(&self) -> (usize, usize, usize, bool, bool, usize) {
+        (
+            self.params.c,
+            self.params.marker_c,
+            self.params.k,
+            self.params.use_aa,
+            self.params.use_syncs,
+            self.params.orf_size,
+        )
+    }
+
+    fn args<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        (
+            self.params.c,
+            self.params.marker_c,
+            self.params.k,
+            self.params.use_aa,
+        )
+            .into_pyobject(py)
+    }
+}
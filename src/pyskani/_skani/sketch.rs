@@ -1,10 +1,106 @@
+use std::sync::Arc;
+
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::types::PyTuple;
+use pyo3::types::PyType;
+use skani::params::SketchParams;
+
+use super::params::SketchParameters;
+use super::utils::Text;
 
 /// A sketched genome.
 #[pyclass(module = "pyskani._skani")]
 #[derive(Clone)]
 pub struct Sketch {
-    sketch: skani::types::Sketch,
+    // shared so that sketches can be cloned cheaply, e.g. when cached
+    sketch: Arc<skani::types::Sketch>,
+}
+
+impl Sketch {
+    /// Sketch a genome from its contigs with the given parameters.
+    ///
+    /// Contigs are named after the genome and their index.
+    pub fn new<'c, C>(params: &SketchParams, name: String, contigs: C, seed: bool) -> PyResult<Self>
+    where
+        C: IntoIterator<Item = &'c [u8]>,
+    {
+        let contigs = contigs
+            .into_iter()
+            .enumerate()
+            .map(|(i, contig)| (format!("{}_{}", &name, i), contig))
+            .collect::<Vec<_>>();
+        Self::from_named_contigs(params, name, contigs, seed)
+    }
+
+    /// Sketch a genome from its named contigs with the given parameters.
+    pub fn from_named_contigs<'c, C>(
+        params: &SketchParams,
+        name: String,
+        contigs: C,
+        seed: bool,
+    ) -> PyResult<Self>
+    where
+        C: IntoIterator<Item = (String, &'c [u8])>,
+    {
+        // Adapted for a single genome from `fastx_to_sketches`
+        // let mut is_valid = false;
+        let mut contig_count = 0;
+        let mut sketch = skani::types::Sketch::new(
+            params.marker_c,
+            params.c,
+            params.k,
+            name.clone(), // file name
+            params.use_aa,
+        );
+
+        // Seed the contigs, making sure a panic in `skani` does not unwind
+        // into the interpreter
+        super::utils::catch_panic("sketching", format_args!("{:?}", name), || {
+            for (contig_name, contig) in contigs {
+                if contig.len() >= skani::params::MIN_LENGTH_CONTIG {
+                    sketch.contigs.push(contig_name);
+                    sketch
+                        .contig_lengths
+                        .push(contig.len() as skani::types::GnPosition);
+                    sketch.total_sequence_length += contig.len();
+                    if params.use_aa {
+                        // find the open reading frames in all six frames, and
+                        // seed the amino-acid k-mers of their translations
+                        let orfs = skani::seeding::get_orfs(contig, params);
+                        skani::seeding::fmh_seeds_aa_with_orf(
+                            contig,
+                            params,
+                            contig_count,
+                            &mut sketch,
+                            orfs,
+                            seed,
+                        );
+                    } else {
+                        skani::seeding::fmh_seeds(contig, params, contig_count, &mut sketch, seed);
+                    }
+                    contig_count += 1;
+                    // is_valid = true;
+                }
+            }
+        })?;
+
+        // NOTE(@althonos): This was removed in skani v0.3.0.
+        // if is_valid && sketch.total_sequence_length > skani::params::REPET_KMER_THRESHOLD {
+        //     sketch.repetitive_kmers =
+        //         skani::seeding::get_repetitive_kmers(&sketch.kmer_seeds_k, sketch.c);
+        // }
+
+        Ok(Self::from(sketch))
+    }
+
+    /// Get a copy of the sketch with a different genome name.
+    pub fn renamed(&self, name: String) -> Self {
+        let mut sketch = skani::types::Sketch::clone(&self.sketch);
+        sketch.file_name = name;
+        Self::from(sketch)
+    }
 }
 
 impl AsRef<skani::types::Sketch> for Sketch {
@@ -15,24 +111,117 @@ impl AsRef<skani::types::Sketch> for Sketch {
 
 #[pymethods]
 impl Sketch {
+    /// Sketch a genome from its contigs.
+    ///
+    /// Arguments:
+    ///     name (`str`): The name of the genome to sketch.
+    ///     contigs (`str`, `bytes`, `bytearray` or `memoryview`): The contigs
+    ///         of the genome.
+    ///
+    /// Keyword Arguments:
+    ///     parameters (`~pyskani.SketchParameters`): The parameters to
+    ///         use for sketching. Cannot be combined with the
+    ///         ``compression``, ``marker_compression`` and ``k`` arguments.
+    ///     compression (`int`): The compression factor for sketches.
+    ///     marker_compression (`int`): The compression factor for marker
+    ///         k-mers.
+    ///     k (`int`): The k-mer size to use for sketching.
+    ///     seed (`bool`): Compute seed positions while sketching.
+    ///
+    /// Returns:
+    ///     `~pyskani.Sketch`: The sketch of the genome, which can be added
+    ///     to or used to query any `~pyskani.Database` created with the
+    ///     same parameters.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[classmethod]
+    #[allow(unused)]
+    #[pyo3(signature = (name, *contigs, parameters=None, compression=None, marker_compression=None, k=None, seed=true))]
+    pub fn from_contigs<'py>(
+        cls: &Bound<'py, PyType>,
+        name: String,
+        contigs: &Bound<'py, PyTuple>,
+        parameters: Option<PyRef<'py, SketchParameters>>,
+        compression: Option<usize>,
+        marker_compression: Option<usize>,
+        k: Option<usize>,
+        seed: bool,
+    ) -> PyResult<Self> {
+        let params =
+            SketchParameters::resolve(parameters.as_deref(), compression, marker_compression, k)?;
+        // Get a view on the contigs
+        let contents = contigs
+            .into_iter()
+            .map(|item| Text::new(item.as_borrowed()))
+            .collect::<PyResult<Vec<_>>>()?;
+        let views = contents.iter().map(|text| text.as_bytes());
+        // Release the GIL while sketching
+        let py = contigs.py();
+        py.allow_threads(|| Self::new(&params, name, views, seed))
+    }
+
+    /// Reduce the sketch for pickling.
+    pub fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyBytes>,))> {
+        let py = slf.py();
+        let state = match bincode::serialize(&*slf.borrow().sketch) {
+            Ok(state) => state,
+            Err(err) => return Err(PyValueError::new_err(err.to_string())),
+        };
+        let from_state = slf.get_type().getattr(pyo3::intern!(py, "_from_state"))?;
+        Ok((from_state, (PyBytes::new(py, &state),)))
+    }
+
+    /// Restore a sketch from its serialized state after unpickling.
+    #[classmethod]
+    #[pyo3(name = "_from_state")]
+    pub fn from_state(_cls: &Bound<'_, PyType>, state: &Bound<'_, PyBytes>) -> PyResult<Self> {
+        match bincode::deserialize::<skani::types::Sketch>(state.as_bytes()) {
+            Ok(sketch) => Ok(Self::from(sketch)),
+            Err(err) => Err(PyValueError::new_err(err.to_string())),
+        }
+    }
+
+    /// `str`: The name of the sketched genome.
     #[getter]
     fn get_name(&self) -> &str {
         &self.sketch.file_name
     }
 
+    /// `int`: The compression factor used for sketching.
     #[getter]
     fn get_c(&self) -> usize {
         self.sketch.c
     }
 
+    /// `bool`: Whether the genome was sketched with amino-acid k-mers.
     #[getter]
     fn get_amino_acid(&self) -> bool {
         self.sketch.amino_acid
     }
+
+    /// `~pyskani.SketchParameters`: The parameters used for sketching.
+    ///
+    /// .. versionadded:: 0.3.0
+    ///
+    #[getter]
+    fn get_parameters(&self) -> SketchParameters {
+        SketchParameters::from(SketchParams::new(
+            self.sketch.marker_c,
+            self.sketch.c,
+            self.sketch.k,
+            false,
+            self.sketch.amino_acid,
+        ))
+    }
 }
 
 impl From<skani::types::Sketch> for Sketch {
     fn from(sketch: skani::types::Sketch) -> Self {
-        Self { sketch }
+        Self {
+            sketch: Arc::new(sketch),
+        }
     }
 }
@@ -1,11 +1,25 @@
+use std::collections::HashMap;
 use std::fs::File;
+use std::fs::TryLockError;
 use std::io::BufReader;
 use std::io::BufWriter;
+use std::io::Write;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use memmap2::Mmap;
+use needletail::errors::ParseErrorKind;
+use rayon::ThreadPool;
 
 use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyBlockingIOError;
 use pyo3::exceptions::PyOSError;
 use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::pybacked::PyBackedBytes;
 use pyo3::pybacked::PyBackedStr;
@@ -13,12 +27,31 @@ use pyo3::types::PyByteArray;
 use pyo3::types::PyBytes;
 use pyo3::types::PyString;
 
+use super::SkaniError;
+
+/// Try to obtain a path from a Python object.
+///
+/// On Unix, the path is obtained with `os.fsencode`, so that file names
+/// that are not valid UTF-8 are supported.
+#[cfg(unix)]
+pub fn fspath<'py>(object: &Bound<'py, PyAny>) -> PyResult<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    let py = object.py();
+    let encoded = py
+        .import(pyo3::intern!(py, "os"))?
+        .call_method1(pyo3::intern!(py, "fsencode"), (object,))?;
+    let bytes = encoded.downcast::<PyBytes>()?;
+    Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes.as_bytes())))
+}
+
 /// Try to obtain a path from a Python object using `os.fsdecode`.
-pub fn fsdecode<'py>(object: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyString>> {
+#[cfg(not(unix))]
+pub fn fspath<'py>(object: &Bound<'py, PyAny>) -> PyResult<PathBuf> {
     let py = object.py();
-    py.import(pyo3::intern!(py, "os"))?
-        .call_method1(pyo3::intern!(py, "fsdecode"), (object,))
-        .and_then(|x| x.extract())
+    let decoded = py
+        .import(pyo3::intern!(py, "os"))?
+        .call_method1(pyo3::intern!(py, "fsdecode"), (object,))?;
+    Ok(PathBuf::from(decoded.downcast::<PyString>()?.to_str()?))
 }
 
 /// Try to open a path or fail with Python error handling.
@@ -51,6 +84,256 @@ pub fn buffered_create(path: &Path) -> PyResult<BufWriter<File>> {
     }
 }
 
+/// Try to memory-map a file or fail with Python error handling.
+///
+/// The file is only mapped if it is at least `length` bytes long, and
+/// `None` is returned otherwise, so that reading the missing bytes
+/// fails with an error instead of a bus error.
+pub fn mmap_open(path: &Path, length: u64) -> PyResult<Option<Mmap>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            return if let Some(code) = err.raw_os_error() {
+                let msg = format!("Failed to open {}", path.display());
+                Err(PyOSError::new_err((code, msg)))
+            } else {
+                Err(PyRuntimeError::new_err(err.to_string()))
+            }
+        }
+    };
+    if file.metadata()?.len() < length {
+        return Ok(None);
+    }
+    // SAFETY: the mapped file is only modified by appending to it, which
+    //         does not affect the mapped region, or replaced by renaming,
+    //         which does not affect the mapped inode. Truncating the file
+    //         or modifying it in place while it is mapped is undefined
+    //         behaviour, which is documented in `Database.open`.
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => Ok(Some(mmap)),
+        Err(err) => Err(PyOSError::new_err(format!(
+            "Failed to map {}: {}",
+            path.display(),
+            err
+        ))),
+    }
+}
+
+/// Get the path of the temporary file used to write a file atomically.
+pub fn temporary_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Flush a buffered writer and synchronize the file contents to disk.
+pub fn sync(writer: BufWriter<File>) -> PyResult<()> {
+    let file = writer.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Rename a temporary file to its final path without synchronizing it.
+fn rename(temporary: &Path, path: &Path) -> PyResult<()> {
+    match std::fs::rename(temporary, path) {
+        Ok(()) => Ok(()),
+        Err(err) => match err.raw_os_error() {
+            Some(code) => {
+                let msg = format!("Failed to replace {}", path.display());
+                Err(PyOSError::new_err((code, msg)))
+            }
+            None => Err(PyRuntimeError::new_err(err.to_string())),
+        },
+    }
+}
+
+/// Replace a file with a temporary file that was synchronized to disk.
+pub fn replace(temporary: &Path, path: &Path) -> PyResult<()> {
+    rename(temporary, path)?;
+    // synchronize the folder as well so that the rename is persisted
+    #[cfg(unix)]
+    if let Some(folder) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        File::open(folder)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Synchronize several files of a folder, and the folder itself, to disk.
+pub fn sync_files<'p, I>(folder: &Path, paths: I) -> PyResult<()>
+where
+    I: IntoIterator<Item = &'p Path>,
+{
+    // files must be opened for writing to be synchronized on Windows
+    for path in paths {
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)?
+            .sync_all()?;
+    }
+    #[cfg(unix)]
+    File::open(folder)?.sync_all()?;
+    Ok(())
+}
+
+/// Write a file atomically, or fail with Python error handling.
+///
+/// The contents are written to a temporary file in the same folder, which
+/// is synchronized to disk and then renamed to the final path, so that a
+/// failure leaves the previous file untouched.
+pub fn atomic_write<F>(path: &Path, f: F) -> PyResult<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> PyResult<()>,
+{
+    let temporary = temporary_path(path);
+    let result = write_temporary(path, f).and_then(|_| replace(&temporary, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Write a file through its temporary file, without synchronizing it.
+///
+/// Unlike `atomic_write`, an interrupted write may leave an empty or
+/// partial file once the system crashes, so the file must be synchronized
+/// with `sync_files` before it is referenced by another file. This avoids
+/// synchronizing the file and its folder for each file written.
+pub fn unsynced_write<F>(path: &Path, f: F) -> PyResult<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> PyResult<()>,
+{
+    let temporary = temporary_path(path);
+    let result = buffered_create(&temporary)
+        .and_then(|mut writer| {
+            f(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        })
+        .and_then(|_| rename(&temporary, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Write the temporary file of a file, and synchronize it to disk.
+///
+/// The temporary file is removed on failure, and must otherwise be
+/// renamed to the final path with `replace` or `replace_all`.
+pub fn write_temporary<F>(path: &Path, f: F) -> PyResult<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> PyResult<()>,
+{
+    let temporary = temporary_path(path);
+    let result = buffered_create(&temporary).and_then(|mut writer| {
+        f(&mut writer)?;
+        sync(writer)
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Get the path of the journal used to replace several files of a folder.
+fn journal_path(folder: &Path) -> PathBuf {
+    folder.join(".journal")
+}
+
+/// Replace several files of a folder at once with their temporary files.
+///
+/// The temporary files must already be synchronized to disk. A journal
+/// listing the files to replace and the files to remove is written before
+/// renaming them, and removed once they were all renamed or removed, so
+/// that an interrupted replacement can be completed with `recover`.
+pub fn replace_all(folder: &Path, replaced: &[&str], removed: &[&str]) -> PyResult<()> {
+    let journal = journal_path(folder);
+    atomic_write(&journal, |writer| {
+        bincode::serialize_into(writer, &(replaced, removed))
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    })?;
+    complete(folder, replaced, removed)?;
+    remove_file(&journal)
+}
+
+/// Rename the temporary files and remove the files listed in a journal.
+fn complete<S: AsRef<str>>(folder: &Path, replaced: &[S], removed: &[S]) -> PyResult<()> {
+    // only accept file names, in case the journal was tampered with
+    let file_name = |name: &S| {
+        let name = Path::new(name.as_ref());
+        match name.file_name() {
+            Some(file_name) if file_name == name.as_os_str() => Ok(folder.join(name)),
+            _ => Err(PyValueError::new_err(format!(
+                "invalid file name in journal: {}",
+                name.display()
+            ))),
+        }
+    };
+    for path in replaced.iter().map(file_name) {
+        let path = path?;
+        let temporary = temporary_path(&path);
+        if temporary.exists() {
+            replace(&temporary, &path)?;
+        }
+    }
+    for path in removed.iter().map(file_name) {
+        let path = path?;
+        if path.exists() {
+            remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Complete or discard an interrupted replacement of the files of a folder.
+///
+/// If the journal was written, the replacement is completed, otherwise
+/// the temporary files of the given files are removed, as well as the
+/// temporary files of any file with one of the given extensions. This
+/// must only be called while holding the folder lock, since the temporary
+/// files of another writer would be affected.
+pub fn recover(folder: &Path, file_names: &[&str], extensions: &[&str]) -> PyResult<()> {
+    let journal = journal_path(folder);
+    if journal.exists() {
+        let reader = buffered_open(&journal)?;
+        let (replaced, removed) =
+            match bincode::deserialize_from::<_, (Vec<String>, Vec<String>)>(reader) {
+                Ok(entries) => entries,
+                Err(err) => return Err(PyValueError::new_err(err.to_string())),
+            };
+        complete(folder, &replaced, &removed)?;
+        remove_file(&journal)?;
+    }
+    for path in file_names
+        .iter()
+        .map(|file_name| folder.join(file_name))
+        .chain(std::iter::once(journal))
+    {
+        let temporary = temporary_path(&path);
+        if temporary.exists() {
+            remove_file(&temporary)?;
+        }
+    }
+    if !extensions.is_empty() {
+        for entry in std::fs::read_dir(folder)? {
+            let path = entry?.path();
+            let is_temporary = path.extension().is_some_and(|ext| ext == "tmp")
+                && Path::new(path.file_stem().unwrap_or_default())
+                    .extension()
+                    .is_some_and(|ext| extensions.iter().any(|e| ext == *e));
+            if is_temporary {
+                remove_file(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check whether a folder has an interrupted replacement to recover.
+pub fn needs_recovery(folder: &Path) -> bool {
+    journal_path(folder).exists()
+}
+
 /// Try to append to a file or fail with Python error handling.
 pub fn buffered_append(path: &Path) -> PyResult<BufWriter<File>> {
     match std::fs::OpenOptions::new()
@@ -71,6 +354,123 @@ pub fn buffered_append(path: &Path) -> PyResult<BufWriter<File>> {
     }
 }
 
+/// Try to remove a file or fail with Python error handling.
+pub fn remove_file(path: &Path) -> PyResult<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            return if let Some(code) = err.raw_os_error() {
+                let msg = format!("Failed to remove {}", path.display());
+                Err(PyOSError::new_err((code, msg)))
+            } else {
+                Err(PyRuntimeError::new_err(err.to_string()))
+            }
+        }
+    }
+}
+
+/// Take an exclusive advisory lock on a database folder.
+///
+/// The lock is held on a `.lock` file inside the folder, and is released
+/// when the returned file is dropped.
+pub fn lock_folder(folder: &Path) -> PyResult<File> {
+    let path = folder.join(".lock");
+    let file = match std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+    {
+        Ok(file) => file,
+        Err(err) => {
+            return if let Some(code) = err.raw_os_error() {
+                let msg = format!("Failed to open {}", path.display());
+                Err(PyOSError::new_err((code, msg)))
+            } else {
+                Err(PyRuntimeError::new_err(err.to_string()))
+            }
+        }
+    };
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(PyBlockingIOError::new_err(format!(
+            "database is locked by another process: {}",
+            folder.display()
+        ))),
+        Err(TryLockError::Error(err)) => {
+            if let Some(code) = err.raw_os_error() {
+                let msg = format!("Failed to lock {}", path.display());
+                Err(PyOSError::new_err((code, msg)))
+            } else {
+                Err(PyRuntimeError::new_err(err.to_string()))
+            }
+        }
+    }
+}
+
+/// Read the records of a FASTA or FASTQ file, possibly compressed.
+///
+/// Records are returned as pairs of identifier and sequence, with
+/// identifiers decoded lossily if they are not valid UTF-8.
+pub fn read_fastx(path: &Path) -> PyResult<Vec<(String, Vec<u8>)>> {
+    let file = buffered_open(path)?;
+    let fastx_error = |err: needletail::errors::ParseError| match err.kind {
+        ParseErrorKind::Io => {
+            PyOSError::new_err(format!("Failed to read {}: {}", path.display(), err.msg))
+        }
+        _ => PyValueError::new_err(format!("Failed to parse {}: {}", path.display(), err)),
+    };
+    let mut reader = needletail::parse_fastx_reader(file).map_err(fastx_error)?;
+    let mut records = Vec::new();
+    while let Some(result) = reader.next() {
+        let record = result.map_err(fastx_error)?;
+        let id = String::from_utf8_lossy(record.id()).into_owned();
+        records.push((id, record.seq().into_owned()));
+    }
+    Ok(records)
+}
+
+/// Get the default genome name for a sequence file.
+///
+/// This is the file stem, after removing any compression extension
+/// (e.g. ``genome`` for ``/data/genome.fna.gz``).
+pub fn genome_name(path: &Path) -> String {
+    let path = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") | Some("bz2") | Some("xz") => path.with_extension(""),
+        _ => path.to_owned(),
+    };
+    match path.file_stem() {
+        Some(stem) => stem.to_string_lossy().into_owned(),
+        None => path.to_string_lossy().into_owned(),
+    }
+}
+
+/// Run a closure calling into ``skani``, converting a panic into an error.
+///
+/// The error names the operation and the genomes it was running on, so
+/// that the offending input can be identified.
+pub fn catch_panic<T, F>(operation: &str, genomes: std::fmt::Arguments, f: F) -> PyResult<T>
+where
+    F: FnOnce() -> T,
+{
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(x) => Ok(x),
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                String::from("unknown error")
+            };
+            Err(SkaniError::new_err(format!(
+                "skani failed while {} {}: {}",
+                operation, genomes, message
+            )))
+        }
+    }
+}
+
 pub enum Text {
     Bytes(PyBackedBytes),
     Str(PyBackedStr),
@@ -92,6 +492,20 @@ impl Text {
         }
     }
 
+    /// Extract the contigs of a genome from a Python object.
+    ///
+    /// A single sequence is accepted as a genome with one contig, any
+    /// other object is iterated to obtain one sequence per contig.
+    pub fn extract_contigs<'py>(object: &Bound<'py, PyAny>) -> PyResult<Vec<Self>> {
+        match Text::new(object.as_borrowed()) {
+            Ok(text) => Ok(vec![text]),
+            Err(_) => object
+                .try_iter()?
+                .map(|item| item.and_then(|contig| Text::new(contig.as_borrowed())))
+                .collect(),
+        }
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         match self {
             Text::Bytes(b) => b.as_ref(),
@@ -105,3 +519,45 @@ impl Text {
 pub fn poisoned_lock_error() -> PyErr {
     PyRuntimeError::new_err("Poisoned lock")
 }
+
+/// The thread pools created for a given number of threads.
+static THREAD_POOLS: OnceLock<Mutex<HashMap<usize, Arc<ThreadPool>>>> = OnceLock::new();
+
+/// Get the module-level thread pool with the given number of threads.
+///
+/// Pools are created on first use and kept for the lifetime of the
+/// module, so that repeated calls do not spawn new threads each time.
+fn thread_pool(threads: usize) -> PyResult<Arc<ThreadPool>> {
+    let mut pools = THREAD_POOLS
+        .get_or_init(Default::default)
+        .lock()
+        .map_err(|_| poisoned_lock_error())?;
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(pool.clone());
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map(Arc::new)
+        .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+    pools.insert(threads, pool.clone());
+    Ok(pool)
+}
+
+/// Run a closure inside a thread pool with the given number of threads.
+///
+/// Passing `0` runs the closure in the default global thread pool, which
+/// is shared by all calls and uses one thread per available CPU. Other
+/// values use a module-level pool shared by all calls with the same
+/// number of threads.
+pub fn install<F, R>(threads: usize, f: F) -> PyResult<R>
+where
+    F: FnOnce() -> R + Send,
+    R: Send,
+{
+    if threads == 0 {
+        Ok(f())
+    } else {
+        Ok(thread_pool(threads)?.install(f))
+    }
+}
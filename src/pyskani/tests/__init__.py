@@ -1,9 +1,11 @@
 from . import (
     test_ani,
-    test_database
+    test_database,
+    test_params,
 )
 
 def load_tests(loader, suite, pattern):
     suite.addTests(loader.loadTestsFromModule(test_ani))
     suite.addTests(loader.loadTestsFromModule(test_database))
+    suite.addTests(loader.loadTestsFromModule(test_params))
     return suite
@@ -1,5 +1,6 @@
 import gzip
 import os
+import pickle
 import tempfile
 import unittest
 
@@ -58,4 +59,249 @@ class TestAniEC590(unittest.TestCase):
         self.assertEqual(len(hits), 1)
         self.assertAlmostEqual(hits[0].reference_fraction, 0.9246, places=4)
         self.assertAlmostEqual(hits[0].query_fraction, 0.9189, places=4)
-        self.assertAlmostEqual(hits[0].identity, 0.9995, places=4)
\ No newline at end of file
+        self.assertAlmostEqual(hits[0].identity, 0.9995, places=4)
+
+    def test_threads(self):
+        hits1 = self.db.query("K12", self.query.seq.encode("ascii"), threads=1)
+        hits2 = self.db.query("K12", self.query.seq.encode("ascii"), threads=2)
+        self.assertEqual(len(hits1), len(hits2))
+        for hit1, hit2 in zip(hits1, hits2):
+            self.assertEqual(hit1.reference_name, hit2.reference_name)
+            self.assertEqual(hit1.identity, hit2.identity)
+            self.assertEqual(hit1.query_fraction, hit2.query_fraction)
+            self.assertEqual(hit1.reference_fraction, hit2.reference_fraction)
+
+    def test_query_many(self):
+        query = self.query.seq.encode("ascii")
+        hits = self.db.query("K12", query)
+        many = self.db.query_many([("K12", [query]), ("EC590", [self.ref.seq])])
+        self.assertEqual(len(many), 2)
+        self.assertEqual(len(many[0]), len(hits))
+        self.assertEqual(many[0][0].query_name, "K12")
+        self.assertEqual(many[0][0].identity, hits[0].identity)
+        self.assertEqual(many[0][0].query_fraction, hits[0].query_fraction)
+        self.assertEqual(many[0][0].reference_fraction, hits[0].reference_fraction)
+        self.assertEqual(len(many[1]), 1)
+        self.assertEqual(many[1][0].query_name, "EC590")
+        self.assertEqual(many[1][0].reference_name, "EC590")
+
+    def test_dist(self):
+        hits = self.db.query("K12", self.query.seq.encode("ascii"))
+        hit = pyskani.dist(
+            self.ref.seq,
+            self.query.seq,
+            reference_name="EC590",
+            query_name="K12",
+        )
+        self.assertEqual(hit.reference_name, "EC590")
+        self.assertEqual(hit.query_name, "K12")
+        self.assertEqual(hit.identity, hits[0].identity)
+        self.assertEqual(hit.query_fraction, hits[0].query_fraction)
+        self.assertEqual(hit.reference_fraction, hits[0].reference_fraction)
+
+    def test_dist_contigs(self):
+        hit1 = pyskani.dist(self.ref.seq, self.query.seq)
+        hit2 = pyskani.dist([self.ref.seq], [self.query.seq.encode("ascii")])
+        self.assertEqual(hit1.identity, hit2.identity)
+
+    def test_triangle(self):
+        db = pyskani.Database()
+        db.sketch("EC590", self.ref.seq)
+        db.sketch("K12", self.query.seq)
+        names, identity, reference_fraction, query_fraction = db.triangle()
+        self.assertEqual(names, ["EC590", "K12"])
+        self.assertEqual(identity[0][0], 1.0)
+        self.assertEqual(identity[1][1], 1.0)
+        self.assertEqual(identity[0][1], identity[1][0])
+        self.assertEqual(reference_fraction[0][1], query_fraction[1][0])
+        self.assertEqual(reference_fraction[1][0], query_fraction[0][1])
+        _, distance, _, _ = db.triangle(distance=True)
+        self.assertAlmostEqual(distance[0][1], 1 - identity[0][1], places=5)
+        self.assertEqual(distance[0][0], 0.0)
+
+    def test_triangle_sparse(self):
+        db = pyskani.Database()
+        db.sketch("EC590", self.ref.seq)
+        db.sketch("K12", self.query.seq)
+        hits = db.triangle(sparse=True)
+        self.assertEqual(len(hits), 1)
+        self.assertEqual(hits[0].reference_name, "EC590")
+        self.assertEqual(hits[0].query_name, "K12")
+        hits = db.triangle(sparse=True, diagonal=True)
+        self.assertEqual(len(hits), 3)
+        self.assertEqual(hits[0].reference_name, "EC590")
+        self.assertEqual(hits[0].query_name, "EC590")
+        self.assertEqual(hits[0].identity, 1.0)
+        distances = db.triangle(sparse=True, diagonal=True, distance=True)
+        self.assertEqual(len(distances), 3)
+        self.assertEqual(distances[0].identity, 0.0)
+        self.assertAlmostEqual(distances[1].identity, 1 - hits[1].identity, places=5)
+
+    def test_query_sketch(self):
+        hits = self.db.query("K12", self.query.seq)
+        sketch = pyskani.Sketch.from_contigs("K12", self.query.seq)
+        self.assertEqual(sketch.name, "K12")
+        hits2 = self.db.query_sketch(sketch)
+        self.assertEqual(len(hits), len(hits2))
+        self.assertEqual(hits[0].identity, hits2[0].identity)
+        self.assertEqual(hits[0].query_fraction, hits2[0].query_fraction)
+        self.assertEqual(hits[0].reference_fraction, hits2[0].reference_fraction)
+
+    def test_pickle(self):
+        sketch = pickle.loads(pickle.dumps(pyskani.Sketch.from_contigs("K12", self.query.seq)))
+        hits = self.db.query_sketch(sketch, est_ci=True)
+        self.assertEqual(len(hits), 1)
+        self.assertEqual(hits[0].query_name, "K12")
+        hit = pickle.loads(pickle.dumps(hits[0]))
+        self.assertEqual(repr(hit), repr(hits[0]))
+        self.assertEqual(hit.ci_lower, hits[0].ci_lower)
+        self.assertEqual(hit.ci_upper, hits[0].ci_upper)
+
+    def test_add(self):
+        db = pyskani.Database()
+        sketch = pyskani.Sketch.from_contigs("EC590", self.ref.seq)
+        db.add(sketch)
+        hits = db.query("K12", self.query.seq)
+        hits2 = self.db.query("K12", self.query.seq)
+        self.assertEqual(len(hits), len(hits2))
+        self.assertEqual(hits[0].reference_name, "EC590")
+        self.assertEqual(hits[0].identity, hits2[0].identity)
+
+    def test_detailed(self):
+        hits = self.db.query("K12", self.query.seq.encode("ascii"))
+        self.assertEqual(len(hits), 1)
+        self.assertEqual(hits[0].query_contig_count, 1)
+        self.assertEqual(hits[0].reference_contig_count, 1)
+        self.assertEqual(hits[0].query_contig_name, "K12_0")
+        self.assertEqual(hits[0].reference_contig_name, "EC590_0")
+        self.assertGreaterEqual(hits[0].standard_deviation, 0.0)
+
+    def test_est_ci(self):
+        hits = self.db.query("K12", self.query.seq.encode("ascii"), est_ci=True)
+        self.assertEqual(len(hits), 1)
+        self.assertLessEqual(hits[0].ci_lower, hits[0].ci_upper)
+        self.assertGreater(hits[0].ci_lower, 0.0)
+
+    def test_individual_contigs(self):
+        hits = self.db.query(
+            "contigs",
+            self.query.seq,
+            "ATGC" * 10,
+            self.ref.seq,
+            individual_contigs=True,
+        )
+        self.assertEqual(len(hits), 2)
+        self.assertEqual(hits[0].query_name, "contigs_0")
+        self.assertEqual(hits[0].query_contig_count, 1)
+        self.assertEqual(hits[0].reference_name, "EC590")
+        self.assertEqual(hits[1].query_name, "contigs_2")
+        self.assertEqual(hits[1].reference_name, "EC590")
+        self.assertGreater(hits[1].identity, hits[0].identity)
+
+    def test_sketch_contigs(self):
+        db = pyskani.Database()
+        sketches = db.sketch_contigs("EC590", "ATGC" * 10, self.ref.seq)
+        self.assertEqual(len(sketches), 1)
+        self.assertEqual(sketches[0].name, "EC590_0")
+        self.assertEqual(db.names(), ["EC590_0"])
+        self.assertEqual(db[sketches[0].name].name, "EC590")
+        hits = db.query("K12", self.query.seq)
+        self.assertEqual(len(hits), 1)
+        self.assertEqual(hits[0].reference_name, "EC590")
+        self.assertEqual(hits[0].reference_contig_name, "EC590_0")
+        other = pyskani.Database()
+        other.add(sketches[0])
+        self.assertEqual(other.names(), ["EC590_0"])
+
+    def test_query_file(self):
+        db = pyskani.Database()
+        sketch = db.sketch_file(os.path.join(DATA_FOLDER, "e.coli-EC590.fasta.gz"))
+        self.assertEqual(sketch.name, "e.coli-EC590")
+        hits = db.query_file(os.path.join(DATA_FOLDER, "e.coli-K12.fasta.gz"))
+        expected = db.query("K12", self.query.seq)
+        self.assertEqual(len(hits), 1)
+        self.assertEqual(hits[0].query_name, "e.coli-K12")
+        self.assertEqual(hits[0].reference_name, "e.coli-EC590")
+        self.assertEqual(hits[0].reference_contig_name.split()[0], self.ref.id)
+        self.assertAlmostEqual(hits[0].identity, expected[0].identity)
+
+    def test_query_file_individual_contigs(self):
+        db = pyskani.Database()
+        db.sketch_file(os.path.join(DATA_FOLDER, "e.coli-EC590.fasta.gz"))
+        path = os.path.join(DATA_FOLDER, "e.coli-K12.fasta.gz")
+        hits = db.query_file(path, individual_contigs=True)
+        self.assertEqual(len(hits), 1)
+        self.assertEqual(hits[0].query_name.split()[0], self.query.id)
+        self.assertEqual(hits[0].reference_name, "e.coli-EC590")
+
+    def test_query_file_missing(self):
+        with self.assertRaises(FileNotFoundError):
+            self.db.query_file(os.path.join(DATA_FOLDER, "missing.fasta"))
+
+    def test_from_files(self):
+        paths = [
+            os.path.join(DATA_FOLDER, "e.coli-EC590.fasta.gz"),
+            os.path.join(DATA_FOLDER, "e.coli-K12.fasta.gz"),
+        ]
+        with tempfile.TemporaryDirectory() as folder:
+            db = pyskani.Database.from_files(paths, folder, threads=2)
+            self.assertTrue(os.path.exists(os.path.join(folder, "markers.bin")))
+            self.assertTrue(os.path.exists(os.path.join(folder, "index.db")))
+            self.assertTrue(os.path.exists(os.path.join(folder, "sketches.db")))
+            db = pyskani.Database.open(folder, mode="r")
+            hits = db.query("K12", self.query.seq)
+            names = sorted(hit.reference_name for hit in hits)
+            self.assertEqual(names, ["e.coli-EC590", "e.coli-K12"])
+
+    def _test_path_names(self, format, sketch_file):
+        # the skani CLI names references after the path to the genome file
+        name = os.path.join("refs", "EC590.fna")
+        sketch = pyskani.Sketch.from_contigs(name, self.ref.seq)
+        with tempfile.TemporaryDirectory() as folder:
+            db = pyskani.Database(folder, format=format)
+            db.add(sketch)
+            db.flush()
+            self.assertTrue(os.path.exists(os.path.join(folder, sketch_file)))
+            for db in (pyskani.Database.open(folder, mode="r"), pyskani.Database.load(folder)):
+                hits = db.query("K12", self.query.seq)
+                self.assertEqual(len(hits), 1)
+                self.assertEqual(hits[0].reference_name, name)
+            with tempfile.TemporaryDirectory() as copy:
+                db.save(copy, overwrite=True)
+                hits = pyskani.Database.open(copy, mode="r").query("K12", self.query.seq)
+                self.assertEqual(len(hits), 1)
+                self.assertEqual(hits[0].reference_name, name)
+
+    def test_path_names_separated(self):
+        self._test_path_names("separated", "EC590.fna.sketch")
+
+    def test_path_names_consolidated(self):
+        self._test_path_names("consolidated", "sketches.db")
+
+    def _test_cli_database(self, folder):
+        # databases created with `skani v0.3.0` from the test folder, see
+        # the "Test fixtures" section of `CONTRIBUTING.md`
+        path = os.path.join(DATA_FOLDER, folder)
+        expected = self.db.query("K12", self.query.seq)
+        for db in (pyskani.Database.open(path, mode="r"), pyskani.Database.load(path)):
+            self.assertEqual(db.names(), ["e.coli-EC590.fasta.gz"])
+            self.assertEqual(db.compression, 125)
+            self.assertEqual(db.marker_compression, 1000)
+            hits = db.query("K12", self.query.seq)
+            self.assertEqual(len(hits), 1)
+            self.assertEqual(hits[0].reference_name, "e.coli-EC590.fasta.gz")
+            self.assertAlmostEqual(hits[0].identity, expected[0].identity, places=5)
+        for format in ("separated", "consolidated"):
+            with tempfile.TemporaryDirectory() as copy:
+                db.save(copy, overwrite=True, format=format)
+                saved = pyskani.Database.open(copy, mode="r")
+                self.assertEqual(saved.names(), db.names())
+                hits = saved.query("K12", self.query.seq)
+                self.assertEqual(len(hits), 1)
+                self.assertAlmostEqual(hits[0].identity, expected[0].identity, places=5)
+
+    def test_cli_database_separated(self):
+        self._test_cli_database("skani-separated")
+
+    def test_cli_database_consolidated(self):
+        self._test_cli_database("skani-consolidated")
@@ -1,5 +1,9 @@
+import io
 import os
 import pathlib
+import pickle
+import shutil
+import struct
 import tempfile
 import unittest
 
@@ -13,6 +17,19 @@ class TestDatabase(unittest.TestCase):
         database.sketch("test genome", b"ATGC"*100)
         self.assertIs(database.path, None)
 
+    def test_sketch(self):
+        database = pyskani.Database()
+        sketch = database.sketch("test genome", b"ATGC"*100)
+        self.assertIsInstance(sketch, pyskani.Sketch)
+        self.assertEqual(sketch.name, "test genome")
+        self.assertEqual(sketch.c, database.compression)
+
+    def test_add_invalid_parameters(self):
+        database = pyskani.Database(compression=125)
+        sketch = pyskani.Sketch.from_contigs("test genome", b"ATGC"*100, compression=200)
+        self.assertRaises(ValueError, database.add, sketch)
+        self.assertRaises(ValueError, database.query_sketch, sketch)
+
     def test_folder_separated(self):
         with tempfile.TemporaryDirectory() as tmpdir:
             database = pyskani.Database(tmpdir, format="separated")
@@ -27,6 +44,18 @@ class TestDatabase(unittest.TestCase):
             self.assertTrue(os.path.exists(os.path.join(tmpdir, "markers.bin")))
             self.assertEqual(database.path, pathlib.Path(tmpdir))
 
+    def test_folder_separated_file_name_collision(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="separated")
+            database.sketch(os.path.join("a", "x.fna"), b"ATGC"*100)
+            self.assertRaises(ValueError, database.sketch, os.path.join("b", "x.fna"), b"TTGC"*100)
+            self.assertEqual(database.names(), [os.path.join("a", "x.fna")])
+        memory = pyskani.Database()
+        memory.sketch(os.path.join("a", "x.fna"), b"ATGC"*100)
+        memory.sketch(os.path.join("b", "x.fna"), b"TTGC"*100)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            self.assertRaises(ValueError, memory.save, tmpdir, format="separated")
+
     def test_folder_consolidated(self):
         with tempfile.TemporaryDirectory() as tmpdir:
             database = pyskani.Database(tmpdir, format="consolidated")
@@ -40,3 +69,604 @@ class TestDatabase(unittest.TestCase):
             self.assertTrue(os.path.exists(os.path.join(tmpdir, "index.db")))
             self.assertTrue(os.path.exists(os.path.join(tmpdir, "markers.bin")))
             self.assertEqual(database.path, pathlib.Path(tmpdir))
+
+    def test_folder_consolidated_reopen(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*100)
+            database.flush()
+            del database
+            database = pyskani.Database.open(tmpdir, mode="a")
+            database.sketch("test2", b"TTGC"*300)
+            database.flush()
+            del database
+            for reopened in (pyskani.Database.open(tmpdir, mode="r"), pyskani.Database.load(tmpdir)):
+                self.assertEqual(reopened.names(), ["test1", "test2"])
+                self.assertEqual(reopened["test1"].name, "test1")
+                self.assertEqual(reopened["test2"].name, "test2")
+
+    def test_flush_no_temporary_files(self):
+        for format in ("separated", "consolidated"):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                database = pyskani.Database(tmpdir, format=format)
+                database.sketch("test1", b"ATGC"*200)
+                database.flush()
+                database.flush()
+                files = os.listdir(tmpdir)
+                self.assertFalse(any(f.endswith(".tmp") for f in files), files)
+                self.assertEqual(pyskani.Database.open(tmpdir, mode="r").names(), ["test1"])
+
+    def test_save_separated(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        database.sketch("test2", b"TTGC"*200)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database.save(tmpdir, format="separated")
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "test1.sketch")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "test2.sketch")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "sketches.db")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "index.db")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "markers.bin")))
+
+    def test_save_consolidated(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        database.sketch("test2", b"TTGC"*200)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database.save(tmpdir)
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "test1.sketch")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "sketches.db")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "index.db")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "markers.bin")))
+
+    def test_save_overwrite(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database.save(tmpdir)
+            self.assertRaises(FileExistsError, database.save, tmpdir)
+            database.sketch("test2", b"TTGC"*200)
+            database.save(tmpdir, overwrite=True, format="separated")
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "sketches.db")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "index.db")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "test2.sketch")))
+
+    def test_save_overwrite_stale_sketches(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        database.sketch("test2", b"TTGC"*200)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database.save(tmpdir, format="separated")
+            database.remove("test1")
+            database.save(tmpdir, overwrite=True, format="separated")
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "test1.sketch")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "test2.sketch")))
+            database.save(tmpdir, overwrite=True, format="consolidated")
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "test2.sketch")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, ".staging")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, ".journal")))
+            self.assertEqual(pyskani.Database.open(tmpdir, mode="r").names(), ["test2"])
+
+    def test_save_stray_sketches(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            stray = os.path.join(tmpdir, "stray.sketch")
+            with open(stray, "wb") as f:
+                f.write(b"data")
+            self.assertRaises(FileExistsError, database.save, tmpdir)
+            self.assertTrue(os.path.exists(stray))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "markers.bin")))
+
+    def test_save_overwrite_failed(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        invalid = pyskani.Database()
+        invalid.sketch(os.path.join("a", "x.fna"), b"ATGC"*100)
+        invalid.sketch(os.path.join("b", "x.fna"), b"TTGC"*100)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database.save(tmpdir, format="separated")
+            self.assertRaises(ValueError, invalid.save, tmpdir, overwrite=True, format="separated")
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, ".staging")))
+            self.assertEqual(pyskani.Database.open(tmpdir, mode="r").names(), ["test1"])
+            self.assertEqual(pyskani.Database.open(tmpdir, mode="r")["test1"].name, "test1")
+
+    def test_save_invalid(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir)
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            self.assertRaises(ValueError, database.save, tmpdir, overwrite=True)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            self.assertRaises(ValueError, pyskani.Database().save, tmpdir, format="invalid")
+
+    def test_remove_memory(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        database.sketch("test2", b"TTGC"*200)
+        database.remove("test1")
+        self.assertRaises(KeyError, database.remove, "test1")
+        del database["test2"]
+        self.assertRaises(KeyError, database.remove, "test2")
+
+    def test_remove_separated(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="separated")
+            database.sketch("test1", b"ATGC"*200)
+            database.sketch("test2", b"TTGC"*200)
+            database.remove("test1")
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "test1.sketch")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "test2.sketch")))
+            del database
+            reopened = pyskani.Database.open(tmpdir, mode="a")
+            self.assertRaises(KeyError, reopened.remove, "test1")
+            reopened.remove("test2")
+
+    def test_remove_consolidated(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*200)
+            database.sketch("test2", b"TTGC"*200)
+            del database["test1"]
+            del database
+            reopened = pyskani.Database.open(tmpdir, mode="a")
+            self.assertRaises(KeyError, reopened.remove, "test1")
+            reopened.remove("test2")
+
+    def test_compact(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*200)
+            database.sketch("test2", b"TTGC"*200)
+            database.sketch("test3", b"TAGC"*200)
+            database.flush()
+            sketches_path = os.path.join(tmpdir, "sketches.db")
+            size = os.stat(sketches_path).st_size
+            self.assertEqual(database.compact(), 0)
+            del database["test2"]
+            reclaimed = database.compact()
+            self.assertGreater(reclaimed, 0)
+            self.assertEqual(os.stat(sketches_path).st_size, size - reclaimed)
+            self.assertFalse(os.path.exists(sketches_path + ".tmp"))
+            reopened = pyskani.Database.open(tmpdir, mode="r")
+            self.assertEqual(reopened.names(), ["test1", "test3"])
+            self.assertEqual(reopened["test1"].name, "test1")
+            self.assertEqual(reopened["test3"].name, "test3")
+
+    @staticmethod
+    def _write_journal(tmpdir, replaced, removed=()):
+        # the journal is a pair of lists of file names encoded with `bincode`
+        def encode(names):
+            data = struct.pack("<Q", len(names))
+            for name in names:
+                data += struct.pack("<Q", len(name.encode())) + name.encode()
+            return data
+        with open(os.path.join(tmpdir, ".journal"), "wb") as f:
+            f.write(encode(replaced) + encode(removed))
+
+    def _interrupted_compaction(self, tmpdir, journal):
+        # compact a copy of the database, and move the compacted files
+        # back as if the compaction was interrupted before the renames
+        database = pyskani.Database(tmpdir, format="consolidated")
+        database.sketch("test1", b"ATGC"*200)
+        database.sketch("test2", b"TTGC"*200)
+        database.flush()
+        del database["test1"]
+        database.flush()
+        del database
+        with tempfile.TemporaryDirectory() as copydir:
+            shutil.copytree(tmpdir, copydir, dirs_exist_ok=True)
+            os.remove(os.path.join(copydir, ".lock"))
+            pyskani.Database.open(copydir, mode="a").compact()
+            for file_name in ("sketches.db", "index.db"):
+                shutil.copy(
+                    os.path.join(copydir, file_name),
+                    os.path.join(tmpdir, file_name + ".tmp"),
+                )
+        if journal:
+            self._write_journal(tmpdir, ["sketches.db", "index.db"])
+        return os.stat(os.path.join(tmpdir, "sketches.db.tmp")).st_size
+
+    def test_compact_interrupted_committed(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            size = self._interrupted_compaction(tmpdir, journal=True)
+            self.assertRaises(BlockingIOError, pyskani.Database.open, tmpdir, mode="r")
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, ".journal")))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "sketches.db.tmp")))
+            reopened = pyskani.Database.open(tmpdir, mode="a")
+            self.assertEqual(reopened.names(), ["test2"])
+            self.assertEqual(reopened["test2"].name, "test2")
+            self.assertEqual(os.stat(os.path.join(tmpdir, "sketches.db")).st_size, size)
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, ".journal")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "sketches.db.tmp")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "index.db.tmp")))
+
+    def test_compact_interrupted_uncommitted(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            size = self._interrupted_compaction(tmpdir, journal=False)
+            reopened = pyskani.Database.open(tmpdir, mode="a")
+            self.assertEqual(reopened.names(), ["test2"])
+            self.assertEqual(reopened["test2"].name, "test2")
+            self.assertGreater(os.stat(os.path.join(tmpdir, "sketches.db")).st_size, size)
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "sketches.db.tmp")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "index.db.tmp")))
+
+    def test_flush_interrupted_committed(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            del database
+            # flush a copy of the database, and move the new files back as
+            # if the flush was interrupted before the renames
+            with tempfile.TemporaryDirectory() as copydir:
+                shutil.copytree(tmpdir, copydir, dirs_exist_ok=True)
+                os.remove(os.path.join(copydir, ".lock"))
+                copy = pyskani.Database.open(copydir, mode="a")
+                copy.sketch("test2", b"TTGC"*200)
+                copy.flush()
+                del copy
+                for file_name in ("sketches.db", "index.db", "markers.bin"):
+                    shutil.copy(
+                        os.path.join(copydir, file_name),
+                        os.path.join(tmpdir, file_name + ".tmp"),
+                    )
+            os.replace(
+                os.path.join(tmpdir, "sketches.db.tmp"),
+                os.path.join(tmpdir, "sketches.db"),
+            )
+            self._write_journal(tmpdir, ["index.db", "markers.bin"])
+            reopened = pyskani.Database.open(tmpdir, mode="a")
+            self.assertEqual(reopened.names(), ["test1", "test2"])
+            self.assertEqual(reopened["test2"].name, "test2")
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, ".journal")))
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "markers.bin.tmp")))
+
+    def test_open_separated_interrupted(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="separated")
+            database.sketch("test1", b"ATGC"*200)
+            database.close()
+            # a sketch file left behind by an interrupted write
+            with open(os.path.join(tmpdir, "test2.sketch.tmp"), "wb") as f:
+                f.write(b"\x00")
+            reopened = pyskani.Database.open(tmpdir, mode="r")
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "test2.sketch.tmp")))
+            reopened = pyskani.Database.open(tmpdir, mode="a")
+            self.assertFalse(os.path.exists(os.path.join(tmpdir, "test2.sketch.tmp")))
+            self.assertEqual(reopened.names(), ["test1"])
+            reopened.sketch("test2", b"TTGC"*200)
+            reopened.close()
+            self.assertEqual(pyskani.Database.open(tmpdir, mode="r")["test2"].name, "test2")
+
+    def test_open_consolidated_truncated(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*200)
+            database.sketch("test2", b"TTGC"*200)
+            database.close()
+            path = os.path.join(tmpdir, "sketches.db")
+            os.truncate(path, os.stat(path).st_size - 1)
+            reopened = pyskani.Database.open(tmpdir, mode="r")
+            self.assertEqual(reopened["test1"].name, "test1")
+            self.assertRaises(OSError, reopened.__getitem__, "test2")
+
+    def test_open_consolidated_add(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            del database
+            reopened = pyskani.Database.open(tmpdir, mode="a")
+            reopened.sketch("test2", b"TTGC"*200)
+            self.assertEqual(reopened["test1"].name, "test1")
+            self.assertEqual(reopened["test2"].name, "test2")
+            del reopened["test1"]
+            reopened.compact()
+            self.assertEqual(reopened["test2"].name, "test2")
+
+    def test_open_read_only(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            opened = pyskani.Database.open(tmpdir, mode="r")
+            self.assertEqual(opened.names(), ["test1"])
+            self.assertRaises(io.UnsupportedOperation, opened.sketch, "test2", b"TTGC"*200)
+            self.assertRaises(io.UnsupportedOperation, opened.remove, "test1")
+            self.assertRaises(io.UnsupportedOperation, opened.compact)
+            opened.flush()
+            self.assertEqual(opened.names(), ["test1"])
+
+    def test_open_default_mode(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            pyskani.Database(tmpdir).close()
+            opened = pyskani.Database.open(tmpdir)
+            opened.sketch("test1", b"ATGC"*200)
+            self.assertRaises(BlockingIOError, pyskani.Database.open, tmpdir, mode="a")
+            opened.close()
+            self.assertEqual(pyskani.Database.open(tmpdir).names(), ["test1"])
+
+    def test_close(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            with pyskani.Database(tmpdir) as database:
+                database.sketch("test1", b"ATGC"*200)
+            self.assertRaises(io.UnsupportedOperation, database.sketch, "test2", b"TTGC"*200)
+            self.assertEqual(database.names(), ["test1"])
+            database.close()
+            writer = pyskani.Database.open(tmpdir, mode="a")
+            self.assertEqual(writer.names(), ["test1"])
+
+    def test_open_invalid_mode(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            pyskani.Database(tmpdir).flush()
+            self.assertRaises(ValueError, pyskani.Database.open, tmpdir, mode="w")
+
+    def test_open_locked(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir)
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            self.assertRaises(BlockingIOError, pyskani.Database.open, tmpdir, mode="a")
+            reader1 = pyskani.Database.open(tmpdir, mode="r")
+            reader2 = pyskani.Database.open(tmpdir, mode="r")
+            self.assertEqual(reader1.names(), reader2.names())
+            del database
+            writer = pyskani.Database.open(tmpdir, mode="a")
+            writer.sketch("test2", b"TTGC"*200)
+
+    def test_save_locked(self):
+        memory = pyskani.Database()
+        memory.sketch("test2", b"TTGC"*200)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir)
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            self.assertRaises(BlockingIOError, memory.save, tmpdir, overwrite=True)
+            self.assertEqual(pyskani.Database.open(tmpdir, mode="r").names(), ["test1"])
+            del database
+            memory.save(tmpdir, overwrite=True)
+            self.assertEqual(pyskani.Database.open(tmpdir, mode="r").names(), ["test2"])
+
+    def test_merge(self):
+        database1 = pyskani.Database()
+        database1.sketch("test1", b"ATGC"*200)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database2 = pyskani.Database(tmpdir, format="consolidated")
+            database2.sketch("test2", b"TTGC"*200)
+            database2.sketch("test3", b"TAGC"*200)
+            self.assertEqual(database1.merge(database2), 2)
+            self.assertEqual(database1.names(), ["test1", "test2", "test3"])
+            self.assertEqual(database1["test3"].name, "test3")
+            self.assertRaises(ValueError, database1.merge, database1)
+
+    def test_merge_duplicates(self):
+        database1 = pyskani.Database()
+        database1.sketch("test1", b"ATGC"*200)
+        database2 = pyskani.Database()
+        database2.sketch("test2", b"TTGC"*200)
+        database2.sketch("test1", b"TAGC"*200)
+        self.assertRaises(ValueError, database1.merge, database2)
+        self.assertEqual(database1.names(), ["test1"])
+        self.assertEqual(database1.merge(database2, duplicates="skip"), 1)
+        self.assertEqual(database1.names(), ["test1", "test2"])
+        self.assertEqual(database1.merge(database2, duplicates="replace"), 2)
+        self.assertEqual(database1.names(), ["test2", "test1"])
+        self.assertRaises(ValueError, database1.merge, database2, duplicates="other")
+
+    def test_from_files_duplicates(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            paths = []
+            for folder, name, seq in [("a", "x", "ATGC"), ("b", "y", "TTGC"), ("c", "x", "TAGC")]:
+                os.mkdir(os.path.join(tmpdir, folder))
+                paths.append(os.path.join(tmpdir, folder, f"{name}.fa"))
+                with open(paths[-1], "w") as f:
+                    f.write(f">{name}\n{seq * 200}\n")
+            out = os.path.join(tmpdir, "out")
+            self.assertRaises(ValueError, pyskani.Database.from_files, paths, out)
+            self.assertFalse(os.path.exists(out))
+            skipped = pyskani.Database.from_files(paths, duplicates="skip")
+            self.assertEqual(skipped.names(), ["x", "y"])
+            replaced = pyskani.Database.from_files(paths, out, duplicates="replace", threads=2)
+            self.assertEqual(replaced.names(), ["y", "x"])
+            self.assertEqual(pyskani.Database.open(out, mode="r").names(), ["y", "x"])
+
+    def test_merge_incompatible(self):
+        database1 = pyskani.Database(k=15)
+        database2 = pyskani.Database(k=13)
+        database2.sketch("test1", b"ATGC"*200)
+        self.assertRaises(ValueError, database1.merge, database2)
+
+    def test_merge_paths(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path1 = os.path.join(tmpdir, "db1")
+            path2 = os.path.join(tmpdir, "db2")
+            out = os.path.join(tmpdir, "out")
+            database1 = pyskani.Database(path1, format="separated")
+            database1.sketch("test1", b"ATGC"*200)
+            database1.flush()
+            database2 = pyskani.Database(path2, format="consolidated")
+            database2.sketch("test2", b"TTGC"*200)
+            database2.flush()
+            merged = pyskani.merge([path1, path2], out)
+            self.assertEqual(merged.names(), ["test1", "test2"])
+            del merged
+            reopened = pyskani.Database.open(out, mode="r")
+            self.assertEqual(reopened.names(), ["test1", "test2"])
+            self.assertEqual(reopened["test2"].name, "test2")
+            self.assertRaises(ValueError, pyskani.merge, [], os.path.join(tmpdir, "empty"))
+
+    def test_merge_paths_invalid(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path1 = os.path.join(tmpdir, "db1")
+            path2 = os.path.join(tmpdir, "db2")
+            path3 = os.path.join(tmpdir, "db3")
+            database1 = pyskani.Database(path1)
+            database1.sketch("test1", b"ATGC"*200)
+            database1.flush()
+            database2 = pyskani.Database(path2)
+            database2.sketch("test1", b"TTGC"*200)
+            database2.flush()
+            database3 = pyskani.Database(path3, compression=100)
+            database3.sketch("test3", b"TTGC"*200)
+            database3.flush()
+            for paths in ([path1, path2], [path1, path3]):
+                out = os.path.join(tmpdir, "out")
+                self.assertRaises(ValueError, pyskani.merge, paths, out)
+                self.assertFalse(os.path.exists(os.path.join(out, "markers.bin")))
+            merged = pyskani.merge([path1, path2], os.path.join(tmpdir, "skip"), duplicates="skip")
+            self.assertEqual(merged.names(), ["test1"])
+
+    @unittest.skipUnless(os.name == "posix", "requires byte file names")
+    def test_non_utf8_path(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = os.path.join(os.fsencode(tmpdir), b"db\xff")
+            database = pyskani.Database(path)
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            del database
+            self.assertEqual(pyskani.Database.open(path, mode="r").names(), ["test1"])
+            self.assertEqual(pyskani.Database.load(path).names(), ["test1"])
+            copy = os.path.join(os.fsencode(tmpdir), b"copy\xfe")
+            pyskani.Database.load(path).save(copy)
+            self.assertEqual(pyskani.Database.open(copy, mode="r").names(), ["test1"])
+
+    def test_compact_memory(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        self.assertEqual(database.compact(), 0)
+
+    def test_cache(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="separated")
+            database.sketch("test1", b"ATGC"*200)
+            database.sketch("test2", b"TTGC"*200)
+            database.flush()
+            del database
+            opened = pyskani.Database.open(tmpdir, mode="a", cache_size=1)
+            self.assertEqual(opened.cache_info(), dict(hits=0, misses=0, maxsize=1, currsize=0))
+            opened["test1"]
+            opened["test1"]
+            opened["test2"]
+            self.assertEqual(opened.cache_info(), dict(hits=1, misses=2, maxsize=1, currsize=1))
+            copy = pickle.loads(pickle.dumps(opened))
+            self.assertEqual(copy.cache_info()["maxsize"], 1)
+            del opened["test2"]
+            self.assertEqual(opened.cache_info()["currsize"], 0)
+
+    def test_cache_disabled(self):
+        database = pyskani.Database()
+        database.sketch("test1", b"ATGC"*200)
+        database["test1"]
+        self.assertEqual(database.cache_info(), dict(hits=0, misses=0, maxsize=0, currsize=0))
+
+    def test_mapping(self):
+        database = pyskani.Database()
+        self.assertEqual(len(database), 0)
+        database.sketch("test1", b"ATGC"*200)
+        database.sketch("test2", b"TTGC"*200)
+        self.assertEqual(len(database), 2)
+        self.assertIn("test1", database)
+        self.assertNotIn("test3", database)
+        self.assertNotIn(1, database)
+        self.assertEqual(list(database), ["test1", "test2"])
+        self.assertEqual(database.names(), ["test1", "test2"])
+        self.assertEqual(database["test2"].name, "test2")
+        self.assertRaises(KeyError, database.__getitem__, "test3")
+        self.assertEqual(database.get("test1").name, "test1")
+        self.assertIs(database.get("test3"), None)
+        self.assertEqual(database.get("test3", 1), 1)
+
+    def test_mapping_folder(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="separated")
+            database.sketch("test1", b"ATGC"*200)
+            self.assertEqual(database["test1"].name, "test1")
+            self.assertIs(database.get("test2"), None)
+            del database["test1"]
+            self.assertEqual(len(database), 0)
+
+    def test_pickle_memory(self):
+        database = pyskani.Database(compression=100)
+        database.sketch("test1", b"ATGC"*200)
+        database.sketch("test2", b"TTGC"*200)
+        copy = pickle.loads(pickle.dumps(database))
+        self.assertIs(copy.path, None)
+        self.assertEqual(copy.compression, 100)
+        self.assertEqual(copy.marker_compression, database.marker_compression)
+        hits = copy.query("test1", b"ATGC"*200)
+        self.assertEqual(
+            [hit.reference_name for hit in hits],
+            [hit.reference_name for hit in database.query("test1", b"ATGC"*200)],
+        )
+
+    def test_pickle_folder(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            copy = pickle.loads(pickle.dumps(database))
+            self.assertEqual(copy.path, pathlib.Path(tmpdir))
+            self.assertTrue(os.path.exists(os.path.join(tmpdir, "markers.bin")))
+            self.assertEqual(
+                [hit.reference_name for hit in copy.query("test1", b"ATGC"*200)],
+                [hit.reference_name for hit in database.query("test1", b"ATGC"*200)],
+            )
+
+    def test_pickle_read_only(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir, format="consolidated")
+            database.sketch("test1", b"ATGC"*200)
+            database.flush()
+            database.sketch("test2", b"TTGC"*200)
+            mtime = os.stat(os.path.join(tmpdir, "markers.bin")).st_mtime_ns
+            copy = pickle.loads(pickle.dumps(database))
+            self.assertEqual(copy.names(), ["test1"])
+            self.assertEqual(os.stat(os.path.join(tmpdir, "markers.bin")).st_mtime_ns, mtime)
+            del database
+            opened = pyskani.Database.open(tmpdir, mode="r")
+            self.assertEqual(pickle.loads(pickle.dumps(opened)).names(), ["test1"])
+
+    def test_setstate_initialized(self):
+        memory = pyskani.Database()
+        memory.sketch("test1", b"ATGC"*200)
+        _, _, state = memory.__reduce__()
+        self.assertRaises(ValueError, memory.__setstate__, state)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            database = pyskani.Database(tmpdir)
+            self.assertRaises(ValueError, database.__setstate__, state)
+            self.assertEqual(database.path, pathlib.Path(tmpdir))
+
+    def test_pickle_sketch(self):
+        sketch = pyskani.Sketch.from_contigs("test genome", b"ATGC"*200)
+        copy = pickle.loads(pickle.dumps(sketch))
+        self.assertEqual(copy.name, sketch.name)
+        self.assertEqual(copy.c, sketch.c)
+        self.assertEqual(copy.amino_acid, sketch.amino_acid)
+
+    def test_pickle_hit(self):
+        hit = pyskani.Hit(
+            0.99,
+            "query",
+            0.8,
+            "reference",
+            0.7,
+            query_contig_name="contig1",
+            query_contig_count=3,
+            reference_contig_count=2,
+            reference_contig_length_50=1000.0,
+            standard_deviation=0.5,
+        )
+        copy = pickle.loads(pickle.dumps(hit))
+        self.assertEqual(copy.identity, hit.identity)
+        self.assertEqual(copy.query_name, hit.query_name)
+        self.assertEqual(copy.query_fraction, hit.query_fraction)
+        self.assertEqual(copy.reference_name, hit.reference_name)
+        self.assertEqual(copy.reference_fraction, hit.reference_fraction)
+        self.assertEqual(copy.query_contig_name, "contig1")
+        self.assertEqual(copy.reference_contig_name, "reference")
+        self.assertEqual(copy.query_contig_count, 3)
+        self.assertEqual(copy.reference_contig_count, 2)
+        self.assertEqual(copy.reference_contig_length_50, 1000.0)
+        self.assertEqual(copy.standard_deviation, 0.5)
+        self.assertEqual(repr(copy), repr(hit))
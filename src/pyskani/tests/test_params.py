@@ -0,0 +1,84 @@
+import gzip
+import os
+import pickle
+import unittest
+
+import pyskani
+from . import fasta
+
+DATA_FOLDER = os.path.realpath(os.path.join(__file__, os.pardir))
+
+
+class TestSketchParameters(unittest.TestCase):
+
+    def test_default(self):
+        params = pyskani.SketchParameters()
+        self.assertEqual(params.compression, 125)
+        self.assertEqual(params.marker_compression, 1000)
+        self.assertEqual(params.k, 15)
+        self.assertFalse(params.amino_acid)
+        self.assertFalse(params.use_syncs)
+
+    def test_invalid(self):
+        self.assertRaises(ValueError, pyskani.SketchParameters, compression=0)
+        self.assertRaises(ValueError, pyskani.SketchParameters, marker_compression=0)
+        self.assertRaises(ValueError, pyskani.SketchParameters, k=0)
+        self.assertRaises(ValueError, pyskani.SketchParameters, k=64)
+        self.assertRaises(ValueError, pyskani.Database, k=0)
+        self.assertRaises(ValueError, pyskani.Database, compression=0)
+
+    def test_frozen(self):
+        params = pyskani.SketchParameters()
+        with self.assertRaises(AttributeError):
+            params.k = 21
+
+    def test_eq(self):
+        params = pyskani.SketchParameters(compression=200)
+        self.assertEqual(params, pyskani.SketchParameters(compression=200))
+        self.assertNotEqual(params, pyskani.SketchParameters())
+        self.assertEqual(hash(params), hash(pyskani.SketchParameters(compression=200)))
+        self.assertNotEqual(params, 200)
+
+    def test_pickle(self):
+        params = pyskani.SketchParameters(compression=200, k=13)
+        copy = pickle.loads(pickle.dumps(params))
+        self.assertEqual(copy, params)
+        self.assertEqual(repr(copy), repr(params))
+
+    def test_database(self):
+        params = pyskani.SketchParameters(compression=200, marker_compression=500)
+        database = pyskani.Database(parameters=params)
+        self.assertEqual(database.parameters, params)
+        self.assertEqual(database.compression, 200)
+        self.assertEqual(database.marker_compression, 500)
+        self.assertRaises(TypeError, pyskani.Database, parameters=params, k=15)
+
+    def test_sketch(self):
+        params = pyskani.SketchParameters(compression=200)
+        sketch = pyskani.Sketch.from_contigs("test", b"ATGC"*200, parameters=params)
+        self.assertEqual(sketch.parameters, params)
+        database = pyskani.Database(parameters=params)
+        database.add(sketch)
+        self.assertEqual(database["test"].parameters, params)
+
+    def test_amino_acid(self):
+        params = pyskani.SketchParameters(amino_acid=True)
+        self.assertTrue(params.amino_acid)
+        self.assertEqual(params.compression, 15)
+        self.assertEqual(params.k, 6)
+        self.assertRaises(ValueError, pyskani.SketchParameters, k=15, amino_acid=True)
+        self.assertEqual(pickle.loads(pickle.dumps(params)), params)
+
+    @unittest.skipUnless(os.path.exists(DATA_FOLDER), "missing data folder")
+    def test_amino_acid_database(self):
+        with gzip.open(os.path.join(DATA_FOLDER, "e.coli-EC590.fasta.gz"), mode="rt") as f:
+            record = next(fasta.parse(f))
+        params = pyskani.SketchParameters(amino_acid=True)
+        database = pyskani.Database(parameters=params)
+        sketch = database.sketch("test", record.seq)
+        self.assertTrue(sketch.amino_acid)
+        self.assertEqual(sketch.parameters, params)
+        hits = database.query("query", record.seq)
+        self.assertEqual(len(hits), 1)
+        self.assertEqual(hits[0].reference_name, "test")
+        self.assertAlmostEqual(hits[0].identity, 1.0, places=2)